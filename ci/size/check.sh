@@ -0,0 +1,16 @@
+#!/bin/sh
+# Builds the claim/complete fast path for riscv64 and fails if a symbol outgrows its budget.
+set -eu
+cd "$(dirname "$0")"
+cargo build --release --target riscv64gc-unknown-none-elf
+lib=target/riscv64gc-unknown-none-elf/release/libriscv_plic_size.a
+grep -v '^#' budget | while read -r symbol budget; do
+    size=$(nm -S "$lib" 2>/dev/null | awk -v s="$symbol" '$4 == s { print $2 }')
+    if [ -z "$size" ]; then
+        echo "$symbol: not found"
+        exit 1
+    fi
+    size=$(printf '%d' "0x$size")
+    echo "$symbol: $size bytes, budget $budget"
+    [ "$size" -le "$budget" ]
+done
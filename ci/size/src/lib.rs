@@ -0,0 +1,25 @@
+//! Out-of-line copies of the claim/complete fast path, measured by `check.sh`.
+
+#![no_std]
+
+use core::num::NonZeroU32;
+
+use riscv_plic::Plic;
+use riscv_plic::context::ContextHandle;
+
+/// See [`Plic::claim_fast`].
+#[unsafe(no_mangle)]
+pub fn plic_claim_fast(plic: &mut Plic, handle: ContextHandle) -> Option<NonZeroU32> {
+    plic.claim_fast(handle)
+}
+
+/// See [`Plic::complete_fast`].
+#[unsafe(no_mangle)]
+pub fn plic_complete_fast(plic: &mut Plic, handle: ContextHandle, source: NonZeroU32) {
+    plic.complete_fast(handle, source)
+}
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
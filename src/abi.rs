@@ -0,0 +1,140 @@
+//! Stable `#[repr(C)]` layouts for exchanging PLIC state with C code and plugins.
+//!
+//! Every structure starts with its size in bytes and [`ABI_VERSION`], so the receiving side
+//! can reject layouts it does not understand. Fields are only ever appended; the version is
+//! bumped when that happens.
+
+use core::num::NonZeroU32;
+
+use crate::{EnableSnapshot, Plic, Priority, SOURCE_NUM, U32_BITS};
+
+/// Version of the layouts in this module.
+pub const ABI_VERSION: u32 = 1;
+
+/// What a PLIC implements, as probed by [`RawCapabilities::probe`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RawCapabilities {
+    /// `size_of::<RawCapabilities>()`.
+    pub size: u32,
+    /// [`ABI_VERSION`].
+    pub version: u32,
+    /// Number of implemented sources.
+    pub ndev: u32,
+    /// Number of valid contexts.
+    pub contexts: u32,
+    /// Largest priority the probed source accepts, 0 if it has none.
+    pub max_priority: u32,
+    /// Largest threshold the probed context accepts.
+    pub max_threshold: u32,
+}
+
+impl RawCapabilities {
+    /// Probes `plic` using the priority of `source` and the threshold of `context`, both of
+    /// which are restored afterwards.
+    #[cold]
+    pub fn probe(plic: &mut Plic, source: NonZeroU32, ctx: usize) -> Self {
+        let threshold = plic.get_threshold(ctx);
+        let max_threshold = plic.probe_threshold_bits(ctx);
+        plic.set_threshold(ctx, threshold);
+        Self {
+            size: size_of::<Self>() as u32,
+            version: ABI_VERSION,
+            ndev: plic.ndev(),
+            contexts: plic.num_contexts as u32,
+            max_priority: Priority::max_for(plic, source).map_or(0, Priority::get),
+            max_threshold,
+        }
+    }
+}
+
+/// The enable bits and threshold of one context.
+#[repr(C)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RawContextState {
+    /// `size_of::<RawContextState>()`.
+    pub size: u32,
+    /// [`ABI_VERSION`].
+    pub version: u32,
+    /// The context.
+    pub context: u32,
+    /// Its threshold.
+    pub threshold: u32,
+    /// Enable bit of source `i` in bit `i % 32` of word `i / 32`.
+    pub enables: [u32; SOURCE_NUM / U32_BITS],
+}
+
+impl RawContextState {
+    /// Captures the state of `context`.
+    #[cold]
+    pub fn save(plic: &Plic, ctx: usize) -> Self {
+        Self {
+            size: size_of::<Self>() as u32,
+            version: ABI_VERSION,
+            context: ctx as u32,
+            threshold: plic.get_threshold(ctx),
+            enables: plic.save_enables(ctx).words,
+        }
+    }
+
+    /// Returns whether the header matches the layout of this crate.
+    pub const fn is_valid(&self) -> bool {
+        self.size as usize == size_of::<Self>() && self.version == ABI_VERSION
+    }
+
+    /// Programs the saved state into its context, or returns `false` without touching the
+    /// PLIC if the header does not match or the context is rejected by
+    /// [`Plic::check_context`].
+    #[cold]
+    pub fn restore(&self, plic: &mut Plic) -> bool {
+        let ctx = self.context as usize;
+        if !self.is_valid() || plic.check_context(ctx).is_err() {
+            return false;
+        }
+        plic.restore_enables(ctx, &EnableSnapshot::from(self));
+        plic.set_threshold(ctx, self.threshold);
+        true
+    }
+}
+
+impl From<&RawContextState> for EnableSnapshot {
+    fn from(state: &RawContextState) -> Self {
+        Self {
+            words: state.enables,
+        }
+    }
+}
+
+// The layouts are part of the ABI.
+const _: () = {
+    assert!(size_of::<RawCapabilities>() == 24);
+    assert!(size_of::<RawContextState>() == 16 + 128);
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CONTEXT_NUM;
+    use crate::testing::{host_plic, irq, registers};
+
+    #[test]
+    fn restore_programs_only_valid_contexts() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, false);
+        plic.enable(irq(5), 1);
+        plic.set_threshold(1, 2);
+        let mut state = RawContextState::save(&plic, 1);
+
+        state.context = 2;
+        assert!(state.restore(&mut plic));
+        assert!(plic.is_enabled(irq(5), 2));
+        assert_eq!(plic.get_threshold(2), 2);
+
+        state.context = CONTEXT_NUM as u32;
+        assert!(!state.restore(&mut plic));
+        state.context = u32::MAX;
+        assert!(!state.restore(&mut plic));
+    }
+}
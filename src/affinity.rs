@@ -0,0 +1,186 @@
+//! Per-source interrupt affinity, akin to `/proc/irq/N/smp_affinity`.
+//!
+//! An [`AffinityTable`] records which harts each source may be delivered to. Setting an
+//! affinity does not touch the PLIC; [`AffinityTable::contexts`] translates it into contexts
+//! for the code that does. [`IrqManager::request`](crate::manager::IrqManager::request)
+//! requests sources on those contexts, a [`Balancer`](crate::balance::Balancer) can take it as
+//! its `affinity` callback, and [`AffinityTable::migrate_off_hart`] follows it when a hart
+//! goes offline.
+
+use core::num::NonZeroU32;
+
+use crate::context::{ContextMap, ContextMask, Mode};
+use crate::{Plic, PlicError};
+
+/// A set of harts `0..64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HartMask(u64);
+
+impl HartMask {
+    /// Number of harts a mask can hold.
+    pub const CAPACITY: usize = u64::BITS as usize;
+    /// All harts.
+    pub const ALL: Self = Self(!0);
+
+    /// The empty set.
+    #[inline]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Creates a mask from its raw bits, bit `i` standing for hart `i`.
+    #[inline]
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw bits.
+    #[inline]
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Returns `self` with `hart` added.
+    #[inline]
+    pub const fn with(self, hart: usize) -> Self {
+        assert!(hart < Self::CAPACITY);
+        Self(self.0 | 1 << hart)
+    }
+
+    /// Returns `self` with `hart` removed.
+    #[inline]
+    pub const fn without(self, hart: usize) -> Self {
+        assert!(hart < Self::CAPACITY);
+        Self(self.0 & !(1 << hart))
+    }
+
+    /// Returns whether `hart` is in the set.
+    #[inline]
+    pub const fn contains(self, hart: usize) -> bool {
+        hart < Self::CAPACITY && self.0 & (1 << hart) != 0
+    }
+
+    /// Returns the harts in both sets.
+    #[inline]
+    pub const fn and(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+/// Desired hart affinity of sources `0..S`. Every source starts with [`HartMask::ALL`].
+pub struct AffinityTable<const S: usize> {
+    masks: [HartMask; S],
+}
+
+impl<const S: usize> AffinityTable<S> {
+    /// Creates a table allowing every source on every hart.
+    pub const fn new() -> Self {
+        Self {
+            masks: [HartMask::ALL; S],
+        }
+    }
+
+    /// Sets the harts interrupt `source` may be delivered to.
+    ///
+    /// Fails with [`PlicError::InvalidSource`] for a source beyond the table, which stays
+    /// allowed everywhere.
+    pub fn set_affinity(&mut self, source: NonZeroU32, mask: HartMask) -> Result<(), PlicError> {
+        let slot = self
+            .masks
+            .get_mut(source.get() as usize)
+            .ok_or(PlicError::InvalidSource {
+                source: source.get(),
+            })?;
+        *slot = mask;
+        Ok(())
+    }
+
+    /// Returns the harts interrupt `source` may be delivered to.
+    ///
+    /// Sources beyond the table are allowed everywhere.
+    #[inline]
+    pub fn affinity(&self, source: NonZeroU32) -> HartMask {
+        self.masks
+            .get(source.get() as usize)
+            .copied()
+            .unwrap_or(HartMask::ALL)
+    }
+
+    /// Translates the affinity of interrupt `source` into the `mode` contexts of `map`.
+    pub fn contexts(&self, source: NonZeroU32, map: &ContextMap<'_>, mode: Mode) -> ContextMask {
+        let harts = self.affinity(source);
+        map.contexts()
+            .filter(|(ctx, t)| {
+                t.mode == mode && harts.contains(t.hart) && *ctx < ContextMask::CAPACITY
+            })
+            .fold(ContextMask::empty(), |mask, (ctx, _)| mask.with(ctx))
+    }
+
+    /// Moves every source among `1..S` enabled on the `mode` context of `hart` to another
+    /// hart, for taking `hart` offline.
+    ///
+    /// Each source is re-enabled on the lowest-numbered hart of `online` its affinity allows,
+    /// skipping sources already enabled elsewhere. A source with no allowed online hart is left
+    /// disabled. Returns the number of sources that were moved.
+    pub fn migrate_off_hart(
+        &self,
+        plic: &mut Plic,
+        map: &ContextMap<'_>,
+        mode: Mode,
+        hart: usize,
+        online: HartMask,
+    ) -> usize {
+        let Some(from) = map.context_of(hart, mode) else {
+            return 0;
+        };
+        let online = online.without(hart);
+        let mut moved = 0;
+        for source in (1..S as u32).filter_map(NonZeroU32::new) {
+            if !plic.is_enabled(source, from) {
+                continue;
+            }
+            plic.disable(source, from);
+            let elsewhere = map
+                .contexts()
+                .any(|(ctx, t)| t.mode == mode && plic.is_enabled(source, ctx));
+            if elsewhere {
+                continue;
+            }
+            let to = self
+                .contexts(source, map, mode)
+                .iter()
+                .find(|&ctx| map.target(ctx).is_some_and(|t| online.contains(t.hart)));
+            if let Some(to) = to {
+                plic.enable(source, to);
+                moved += 1;
+            }
+        }
+        moved
+    }
+}
+
+impl<const S: usize> Default for AffinityTable<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::irq;
+
+    #[test]
+    fn affinity_beyond_the_table_is_refused_and_reads_as_all() {
+        let mut table = AffinityTable::<8>::new();
+        let mask = HartMask::empty().with(1);
+        assert_eq!(table.set_affinity(irq(7), mask), Ok(()));
+        assert_eq!(table.affinity(irq(7)), mask);
+        assert_eq!(
+            table.set_affinity(irq(8), mask),
+            Err(PlicError::InvalidSource { source: 8 })
+        );
+        assert_eq!(table.affinity(irq(8)), HartMask::ALL);
+    }
+}
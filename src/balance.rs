@@ -0,0 +1,100 @@
+//! Claim-count based load balancing of interrupt sources between contexts.
+
+use core::num::NonZeroU32;
+
+use crate::Plic;
+use crate::context::ContextMask;
+use crate::stats::IrqStats;
+
+/// A source moved by [`Balancer::tick`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Migration {
+    /// The migrated source.
+    pub source: NonZeroU32,
+    /// The context it was enabled in.
+    pub from: usize,
+    /// The context it is enabled in now.
+    pub to: usize,
+}
+
+/// Moves hot sources from busy to idle contexts, driven by the kernel tick.
+///
+/// On every [`Balancer::tick`] it computes how many claims each context took since the previous
+/// tick, from an [`IrqStats`] filled by the dispatcher. If the busiest context took more than
+/// `slack` claims over the idlest one, the hottest source routed to the busiest context whose
+/// affinity allows the idlest one is moved there by rewriting its enables. At most one source
+/// moves per tick, so the system settles gradually.
+pub struct Balancer<const S: usize, const C: usize> {
+    /// Claim counts seen at the previous tick.
+    last: [[u32; C]; S],
+    /// Claims since the previous tick.
+    delta: [[u32; C]; S],
+    slack: u32,
+}
+
+impl<const S: usize, const C: usize> Balancer<S, C> {
+    /// Creates a balancer that tolerates a load difference of `slack` claims per tick.
+    pub const fn new(slack: u32) -> Self {
+        Self {
+            last: [[0; C]; S],
+            delta: [[0; C]; S],
+            slack,
+        }
+    }
+
+    /// Runs one balancing round over the contexts in `contexts`.
+    ///
+    /// `affinity` returns the contexts each source may be routed to; sources that must not
+    /// move, e.g. those requested with [`IrqFlags::NO_BALANCE`](crate::dispatch::IrqFlags),
+    /// should return an empty mask. Only sources enabled in exactly one of `contexts` are
+    /// considered.
+    pub fn tick(
+        &mut self,
+        plic: &mut Plic,
+        stats: &IrqStats<S, C>,
+        contexts: ContextMask,
+        affinity: impl Fn(NonZeroU32) -> ContextMask,
+    ) -> Option<Migration> {
+        let mut load = [0u64; C];
+        for source in (0..S as u32).filter_map(NonZeroU32::new) {
+            let i = source.get() as usize;
+            for ctx in contexts.iter().filter(|&ctx| ctx < C) {
+                let claims = stats.claims(source, ctx);
+                self.delta[i][ctx] = claims.wrapping_sub(self.last[i][ctx]);
+                self.last[i][ctx] = claims;
+                load[ctx] += self.delta[i][ctx] as u64;
+            }
+        }
+
+        let in_range = || contexts.iter().filter(|&ctx| ctx < C);
+        let busiest = in_range().max_by_key(|&ctx| load[ctx])?;
+        let idlest = in_range().min_by_key(|&ctx| load[ctx])?;
+        let diff = load[busiest] - load[idlest];
+        if diff <= self.slack as u64 {
+            return None;
+        }
+
+        let (source, _) = (0..S as u32)
+            .filter_map(NonZeroU32::new)
+            .map(|source| (source, self.delta[source.get() as usize][busiest]))
+            .filter(|&(source, hot)| {
+                hot > 0
+                    && (hot as u64) < diff
+                    && affinity(source).contains(idlest)
+                    && contexts
+                        .iter()
+                        .filter(|&ctx| plic.is_enabled(source, ctx))
+                        .eq([busiest])
+            })
+            .max_by_key(|&(_, hot)| hot)?;
+
+        plic.enable(source, idlest);
+        plic.disable(source, busiest);
+        Some(Migration {
+            source,
+            from: busiest,
+            to: idlest,
+        })
+    }
+}
@@ -0,0 +1,459 @@
+//! Portable binary encoding of the PLIC configuration.
+//!
+//! There is no separate configuration type: the blob captures the PLIC itself. [`Plic::export`]
+//! writes the source count, source priorities, for a set of contexts their thresholds and
+//! enables, and the wakeup and quarantine marks of the [`PlicState`](crate::PlicState) into a
+//! versioned blob that [`Plic::import`] programs back. A bootloader can hand the kernel a
+//! pre-programmed PLIC description this way, or a kernel can carry its interrupt state across
+//! kexec.
+//!
+//! The format is little-endian LEB128 varints, as in postcard. Version 3, written by
+//! [`Plic::export`], run-length encodes the priorities, which are mostly identical, and stores
+//! only the non-zero words of the bitmaps, as most bits are zero:
+//!
+//! ```text
+//! "PLIC" 3 ndev
+//! { length priority }              runs covering sources 1..=ndev in order
+//! count { context threshold bitmap }
+//! bitmap                           wakeup sources
+//! bitmap                           quarantined sources
+//! ```
+//!
+//! where a `bitmap` is `count { skip word }` and `skip` the number of all-zero words since
+//! the previous word. [`Plic::import`] still accepts version 2 blobs, which end after the
+//! contexts, and version 1 blobs:
+//!
+//! ```text
+//! "PLIC" 1 ndev
+//! count { source priority }        sources with a non-zero priority
+//! count { context threshold count { source } }
+//! ```
+
+use core::num::NonZeroU32;
+
+use tock_registers::interfaces::Writeable;
+
+use crate::context::ContextMask;
+use crate::{Plic, PlicError, U32_BITS};
+
+/// Leading bytes of every blob.
+const MAGIC: &[u8; 4] = b"PLIC";
+/// Version written by [`Plic::export`].
+pub const VERSION: u8 = 3;
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn byte(&mut self, byte: u8) -> Result<(), PlicError> {
+        *self.buf.get_mut(self.pos).ok_or(PlicError::NoSpace)? = byte;
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn varint(&mut self, mut value: u64) -> Result<(), PlicError> {
+        while value >= 0x80 {
+            self.byte(value as u8 | 0x80)?;
+            value >>= 7;
+        }
+        self.byte(value as u8)
+    }
+
+    /// Writes the non-zero words of a bitmap with the zero words skipped.
+    fn bitmap(&mut self, words: impl Iterator<Item = u32> + Clone) -> Result<(), PlicError> {
+        self.varint(words.clone().filter(|&word| word != 0).count() as u64)?;
+        let mut skip = 0u64;
+        for word in words {
+            if word == 0 {
+                skip += 1;
+                continue;
+            }
+            self.varint(skip)?;
+            self.varint(word.into())?;
+            skip = 0;
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, PlicError> {
+        let (&byte, rest) = self.bytes.split_first().ok_or(PlicError::InvalidBlob)?;
+        self.bytes = rest;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, PlicError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(PlicError::InvalidBlob)
+    }
+
+    fn u32(&mut self) -> Result<u32, PlicError> {
+        u32::try_from(self.varint()?).map_err(|_| PlicError::InvalidBlob)
+    }
+
+    fn usize(&mut self) -> Result<usize, PlicError> {
+        usize::try_from(self.varint()?).map_err(|_| PlicError::InvalidBlob)
+    }
+
+    /// Reads a bitmap written by [`Writer::bitmap`], passing the number of every set bit to
+    /// `visit`.
+    fn bitmap(
+        &mut self,
+        mut visit: impl FnMut(u32) -> Result<(), PlicError>,
+    ) -> Result<(), PlicError> {
+        let mut group = 0usize;
+        for _ in 0..self.usize()? {
+            group = group
+                .checked_add(self.usize()?)
+                .ok_or(PlicError::InvalidBlob)?;
+            let mut word = self.u32()?;
+            while word != 0 {
+                let bit = word.trailing_zeros();
+                word &= word - 1;
+                let n = group
+                    .checked_mul(U32_BITS)
+                    .and_then(|base| u32::try_from(base).ok())
+                    .and_then(|base| base.checked_add(bit))
+                    .ok_or(PlicError::InvalidBlob)?;
+                visit(n)?;
+            }
+            group += 1;
+        }
+        Ok(())
+    }
+}
+
+/// One decoded setting, in blob order.
+enum Entry {
+    Header { version: u8, ndev: u32 },
+    Priority(NonZeroU32, u32),
+    Threshold(usize, u32),
+    Enable(NonZeroU32, usize),
+    Wake(NonZeroU32),
+    Quarantine(NonZeroU32),
+}
+
+/// Decodes `bytes`, passing every setting to `visit`.
+fn walk(
+    bytes: &[u8],
+    mut visit: impl FnMut(Entry) -> Result<(), PlicError>,
+) -> Result<(), PlicError> {
+    let mut r = Reader { bytes };
+    for &expected in MAGIC {
+        if r.byte()? != expected {
+            return Err(PlicError::InvalidBlob);
+        }
+    }
+    let version = r.byte()?;
+    if !matches!(version, 1..=VERSION) {
+        return Err(PlicError::UnsupportedVersion { version });
+    }
+    let ndev = r.u32()?;
+    visit(Entry::Header { version, ndev })?;
+    let valid = |s: u32| {
+        NonZeroU32::new(s)
+            .filter(|s| s.get() <= ndev)
+            .ok_or(PlicError::InvalidBlob)
+    };
+    if version == 1 {
+        for _ in 0..r.usize()? {
+            let s = valid(r.u32()?)?;
+            visit(Entry::Priority(s, r.u32()?))?;
+        }
+    } else {
+        let mut next = 1;
+        while next <= ndev {
+            let (len, value) = (r.u32()?, r.u32()?);
+            let end = next.checked_add(len).filter(|&e| len != 0 && e - 1 <= ndev);
+            let end = end.ok_or(PlicError::InvalidBlob)?;
+            for s in next..end {
+                visit(Entry::Priority(valid(s)?, value))?;
+            }
+            next = end;
+        }
+    }
+    for _ in 0..r.usize()? {
+        let ctx = r.usize()?;
+        visit(Entry::Threshold(ctx, r.u32()?))?;
+        if version == 1 {
+            for _ in 0..r.usize()? {
+                visit(Entry::Enable(valid(r.u32()?)?, ctx))?;
+            }
+        } else {
+            r.bitmap(|s| visit(Entry::Enable(valid(s)?, ctx)))?;
+        }
+    }
+    if version >= 3 {
+        r.bitmap(|s| visit(Entry::Wake(valid(s)?)))?;
+        r.bitmap(|s| visit(Entry::Quarantine(valid(s)?)))?;
+    }
+    if r.bytes.is_empty() {
+        Ok(())
+    } else {
+        Err(PlicError::InvalidBlob)
+    }
+}
+
+impl Plic {
+    /// Encode the source count, the priorities, for each context in `contexts` the threshold
+    /// and enables, and the wakeup and quarantine marks into `buf`.
+    ///
+    /// Returns the length of the blob, or [`PlicError::NoSpace`] if `buf` is too small.
+    /// Without a [`PlicState`](crate::PlicState) no source is marked.
+    #[cold]
+    pub fn export(&self, contexts: ContextMask, buf: &mut [u8]) -> Result<usize, PlicError> {
+        let mut w = Writer { buf, pos: 0 };
+        for &byte in MAGIC {
+            w.byte(byte)?;
+        }
+        w.byte(VERSION)?;
+        w.varint(self.ndev.into())?;
+        let mut run: Option<(u32, u32)> = None;
+        for source in self.sources() {
+            let priority = self.get_priority(source);
+            run = match run {
+                Some((len, value)) if value == priority => Some((len + 1, value)),
+                Some((len, value)) => {
+                    w.varint(len.into())?;
+                    w.varint(value.into())?;
+                    Some((1, priority))
+                }
+                None => Some((1, priority)),
+            };
+        }
+        if let Some((len, value)) = run {
+            w.varint(len.into())?;
+            w.varint(value.into())?;
+        }
+        w.varint(contexts.iter().count() as u64)?;
+        let groups = 0..self.ndev as usize / U32_BITS + 1;
+        for ctx in contexts.iter() {
+            self.check_context(ctx)?;
+            w.varint(ctx as u64)?;
+            w.varint(self.get_threshold(ctx).into())?;
+            let saved = self.save_enables(ctx);
+            w.bitmap(
+                groups
+                    .clone()
+                    .map(|group| saved.words[group] & self.implemented(group)),
+            )?;
+        }
+        w.bitmap(
+            groups
+                .clone()
+                .map(|group| self.wake_word(group) & self.implemented(group)),
+        )?;
+        w.bitmap(groups.map(|group| self.quarantined_word(group) & self.implemented(group)))?;
+        Ok(w.pos)
+    }
+
+    /// Returns the bits of the implemented sources in word `group` of a per-source bitmap.
+    fn implemented(&self, group: usize) -> u32 {
+        (0..U32_BITS)
+            .filter(|bit| (1..=self.ndev as usize).contains(&(group * U32_BITS + bit)))
+            .fold(0, |mask, bit| mask | 1 << bit)
+    }
+
+    /// Program the configuration encoded in `blob` by [`Plic::export`].
+    ///
+    /// Sources without a priority in the blob get priority 0, and the listed contexts have all
+    /// other sources disabled. Blobs of version 3 also replace the wakeup and quarantine marks
+    /// of the implemented sources. The blob is validated completely before any setting is
+    /// written: priorities and thresholds must fit the implemented bits, which are probed and
+    /// restored, and marked sources need a [`PlicState`](crate::PlicState).
+    #[cold]
+    pub fn import(&mut self, blob: &[u8]) -> Result<(), PlicError> {
+        walk(blob, |entry| match entry {
+            Entry::Header { ndev, .. } if ndev as usize >= crate::SOURCE_NUM => {
+                Err(PlicError::InvalidBlob)
+            }
+            // Probing unmasks the source for a moment, so quarantined ones are left alone.
+            Entry::Priority(source, value) if value != 0 && !self.is_quarantined(source) => {
+                let saved = self.get_priority(source);
+                let bits = self.probe_priority_bits(source);
+                self.regs().interrupt_priority[source.get() as usize].set(saved);
+                fits(value, bits)
+            }
+            Entry::Threshold(ctx, value) => {
+                self.check_context(ctx)?;
+                let saved = self.get_threshold(ctx);
+                let bits = self.probe_threshold_bits(ctx);
+                self.context_regs(ctx).priority_threshold.set(saved);
+                fits(value, bits)
+            }
+            Entry::Wake(_) | Entry::Quarantine(_) if self.state.is_none() => {
+                Err(PlicError::NoState)
+            }
+            _ => Ok(()),
+        })?;
+        walk(blob, |entry| {
+            match entry {
+                Entry::Header { version, ndev } => {
+                    self.set_ndev(ndev);
+                    for source in self.sources() {
+                        if version >= 3 && self.state.is_some() {
+                            self.release(source);
+                            self.set_wake(source, false);
+                        }
+                        self.set_priority(source, 0);
+                    }
+                }
+                Entry::Priority(source, value) => self.set_priority(source, value),
+                Entry::Threshold(ctx, value) => {
+                    self.disable_all(ctx);
+                    self.set_threshold(ctx, value);
+                }
+                Entry::Enable(source, ctx) => self.enable(source, ctx),
+                Entry::Wake(source) => self.set_wake(source, true),
+                Entry::Quarantine(source) => self.quarantine(source),
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Checks that the blob value `value` fits the probed register bits `bits`.
+fn fits(value: u32, bits: u32) -> Result<(), PlicError> {
+    if value & !bits == 0 {
+        Ok(())
+    } else {
+        Err(PlicError::UnsupportedPriority { value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::testing::{host_plic, irq, registers};
+
+    /// Sources 3 and 33 have priorities 5 and 2 and are enabled in context 1, whose threshold
+    /// is 4, out of 40 sources.
+    const V1: &[u8] = &[
+        b'P', b'L', b'I', b'C', 1, 40, 2, 3, 5, 33, 2, 1, 1, 4, 2, 3, 33,
+    ];
+    /// [`V1`] in version 2.
+    const V2: &[u8] = &[
+        b'P', b'L', b'I', b'C', 2, 40, 2, 0, 1, 5, 29, 0, 1, 2, 7, 0, 1, 1, 4, 2, 0, 8, 0, 2,
+    ];
+
+    fn export(plic: &Plic) -> Vec<u8> {
+        let mut buf = [0; 256];
+        let len = plic.export(ContextMask::single(1), &mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    fn assert_programmed(plic: &Plic) {
+        for source in (1..=40).map(irq) {
+            let priority = match source.get() {
+                3 => 5,
+                33 => 2,
+                _ => 0,
+            };
+            assert_eq!(plic.get_priority(source), priority);
+            assert_eq!(plic.is_enabled(source, 1), priority != 0);
+        }
+        assert_eq!(plic.get_threshold(1), 4);
+    }
+
+    #[test]
+    fn version_1_and_2_blobs_import_alike() {
+        let expected = [&V2[..4], &[3], &V2[5..], &[0, 0]].concat();
+        for blob in [V1, V2] {
+            let mut memory = registers();
+            let mut plic = host_plic(&mut memory, false);
+            plic.import(blob).unwrap();
+            assert_programmed(&plic);
+            assert_eq!(export(&plic), expected);
+        }
+    }
+
+    #[test]
+    fn export_round_trips_wake_and_quarantine() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        plic.import(V2).unwrap();
+        plic.set_wake(irq(3), true);
+        plic.quarantine(irq(33));
+        let blob = export(&plic);
+
+        let mut memory = registers();
+        let mut copy = host_plic(&mut memory, true);
+        copy.set_wake(irq(5), true);
+        copy.import(&blob).unwrap();
+        assert!(copy.is_wake(irq(3)) && !copy.is_wake(irq(5)));
+        assert!(copy.is_quarantined(irq(33)));
+        assert_eq!(copy.get_priority(irq(33)), 0);
+        assert_eq!(export(&copy), blob);
+    }
+
+    #[test]
+    fn marks_need_a_state() {
+        let mut memory = registers();
+        let plic = host_plic(&mut memory, true);
+        plic.set_wake(irq(3), true);
+        let blob = export(&plic);
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, false);
+        assert_eq!(plic.import(&blob), Err(PlicError::NoState));
+    }
+
+    #[test]
+    fn malformed_blobs_are_rejected_untouched() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, false);
+        for len in 0..V2.len() {
+            assert_eq!(plic.import(&V2[..len]), Err(PlicError::InvalidBlob));
+        }
+        let trailing = [V2, &[0]].concat();
+        assert_eq!(plic.import(&trailing), Err(PlicError::InvalidBlob));
+        let mut magic = V2.to_vec();
+        magic[3] = b'X';
+        assert_eq!(plic.import(&magic), Err(PlicError::InvalidBlob));
+        let mut version = V2.to_vec();
+        version[4] = 9;
+        assert_eq!(
+            plic.import(&version),
+            Err(PlicError::UnsupportedVersion { version: 9 })
+        );
+        let mut source = V1.to_vec();
+        source[9] = 41;
+        assert_eq!(plic.import(&source), Err(PlicError::InvalidBlob));
+        let mut run = V2.to_vec();
+        run[6] = 0;
+        assert_eq!(plic.import(&run), Err(PlicError::InvalidBlob));
+        let ndev = [&V2[..5], &[0x80, 8]].concat();
+        assert_eq!(plic.import(&ndev), Err(PlicError::InvalidBlob));
+        assert_eq!(plic.get_priority(irq(3)), 0);
+        assert_eq!(plic.get_threshold(1), 0);
+    }
+
+    #[test]
+    fn export_needs_space() {
+        let mut memory = registers();
+        let plic = host_plic(&mut memory, false);
+        let mut buf = [0; 8];
+        assert_eq!(
+            plic.export(ContextMask::single(1), &mut buf),
+            Err(PlicError::NoSpace)
+        );
+    }
+}
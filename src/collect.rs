@@ -0,0 +1,43 @@
+//! Gathering iterator output without allocation.
+//!
+//! The list-returning APIs, e.g. [`Plic::enabled_sources`](crate::Plic::enabled_sources) or
+//! [`Plic::in_service_sources`](crate::Plic::in_service_sources), return iterators. These
+//! helpers store their items in a caller-provided slice or `heapless::Vec`, stopping when it
+//! is full instead of panicking.
+
+use core::mem::MaybeUninit;
+
+/// Write the items of `iter` to the start of `buf` until either runs out.
+///
+/// Returns the number of items written.
+pub fn into_slice<T>(iter: impl IntoIterator<Item = T>, buf: &mut [MaybeUninit<T>]) -> usize {
+    let mut n = 0;
+    for (slot, item) in buf.iter_mut().zip(iter) {
+        slot.write(item);
+        n += 1;
+    }
+    n
+}
+
+/// Push the items of `iter` to `out` until either runs out.
+///
+/// No item is taken from `iter` once `out` is full, so nothing is lost when the iterator has
+/// side effects.
+///
+/// Returns the number of items pushed.
+#[cfg(feature = "heapless")]
+pub fn into_vec<T, const N: usize>(
+    iter: impl IntoIterator<Item = T>,
+    out: &mut heapless::Vec<T, N>,
+) -> usize {
+    let mut iter = iter.into_iter();
+    let mut n = 0;
+    while !out.is_full() {
+        let Some(item) = iter.next() else {
+            break;
+        };
+        _ = out.push(item);
+        n += 1;
+    }
+    n
+}
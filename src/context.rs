@@ -0,0 +1,240 @@
+//! Mapping between PLIC contexts and the (hart, privilege mode) pairs they deliver to.
+//!
+//! The PLIC specification leaves the assignment of contexts to harts to the platform. It is
+//! described by the `interrupts-extended` property of the device-tree node, one entry per
+//! context in context order.
+
+use crate::Plic;
+
+/// A RISC-V privilege mode that can receive external interrupts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// Machine mode, `mip.MEIP`.
+    Machine,
+    /// Supervisor mode, `sip.SEIP`.
+    Supervisor,
+}
+
+/// The hart and privilege mode a context delivers its interrupts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ContextTarget {
+    /// Hart ID.
+    pub hart: usize,
+    /// Privilege mode.
+    pub mode: Mode,
+}
+
+impl ContextTarget {
+    /// The machine-mode external interrupt of `hart`.
+    #[inline]
+    pub const fn machine(hart: usize) -> Self {
+        Self {
+            hart,
+            mode: Mode::Machine,
+        }
+    }
+
+    /// The supervisor-mode external interrupt of `hart`.
+    #[inline]
+    pub const fn supervisor(hart: usize) -> Self {
+        Self {
+            hart,
+            mode: Mode::Supervisor,
+        }
+    }
+}
+
+/// The context layout of a platform.
+///
+/// Entry `i` describes context `i`; `None` marks a context that is not connected to any hart.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ContextMap<'a> {
+    targets: &'a [Option<ContextTarget>],
+}
+
+impl<'a> ContextMap<'a> {
+    /// Creates a context map from per-context targets, in context order.
+    #[inline]
+    pub const fn new(targets: &'a [Option<ContextTarget>]) -> Self {
+        Self { targets }
+    }
+
+    /// Returns the number of contexts, including unconnected ones.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Returns whether the map has no contexts.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Returns the target of context `ctx`.
+    #[inline]
+    pub fn target(&self, ctx: usize) -> Option<ContextTarget> {
+        self.targets.get(ctx).copied().flatten()
+    }
+
+    /// Returns the context delivering to `hart` in `mode`.
+    pub fn context_of(&self, hart: usize, mode: Mode) -> Option<usize> {
+        let target = ContextTarget { hart, mode };
+        self.targets.iter().position(|t| *t == Some(target))
+    }
+
+    /// Iterates over the contexts of `hart` as `(mode, context)` pairs, in context order.
+    pub fn contexts_for_hart(&self, hart: usize) -> impl Iterator<Item = (Mode, usize)> + 'a {
+        self.contexts()
+            .filter(move |(_, t)| t.hart == hart)
+            .map(|(ctx, t)| (t.mode, ctx))
+    }
+
+    /// Iterates over the connected contexts as `(context, target)` pairs.
+    pub fn contexts(&self) -> impl Iterator<Item = (usize, ContextTarget)> + 'a {
+        self.targets
+            .iter()
+            .enumerate()
+            .filter_map(|(ctx, t)| t.map(|t| (ctx, t)))
+    }
+}
+
+/// The contexts of one hart, see [`Plic::hart`].
+///
+/// This is a plain value, so it can be stashed in per-CPU data at boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HartContexts {
+    hart: usize,
+    #[cfg(not(feature = "supervisor-only"))]
+    machine: Option<usize>,
+    supervisor: Option<usize>,
+}
+
+impl HartContexts {
+    /// Returns the hart ID.
+    #[inline]
+    pub const fn hart(&self) -> usize {
+        self.hart
+    }
+
+    /// Returns the machine-mode context of the hart, if it has one.
+    #[cfg(not(feature = "supervisor-only"))]
+    #[inline]
+    pub const fn machine(&self) -> Option<usize> {
+        self.machine
+    }
+
+    /// Returns the supervisor-mode context of the hart, if it has one.
+    #[inline]
+    pub const fn supervisor(&self) -> Option<usize> {
+        self.supervisor
+    }
+
+    /// Returns the context of the hart for `mode`.
+    ///
+    /// With the `supervisor-only` feature, this is always `None` for [`Mode::Machine`].
+    #[inline]
+    pub const fn get(&self, mode: Mode) -> Option<usize> {
+        match mode {
+            #[cfg(not(feature = "supervisor-only"))]
+            Mode::Machine => self.machine,
+            #[cfg(feature = "supervisor-only")]
+            Mode::Machine => None,
+            Mode::Supervisor => self.supervisor,
+        }
+    }
+}
+
+impl Plic {
+    /// Returns the contexts of `hart` according to the installed context map.
+    ///
+    /// Both contexts are `None` if no map was installed with [`Plic::set_context_map`].
+    pub fn hart(&self, hart: usize) -> HartContexts {
+        let map = self.context_map();
+        HartContexts {
+            hart,
+            #[cfg(not(feature = "supervisor-only"))]
+            machine: map.and_then(|m| m.context_of(hart, Mode::Machine)),
+            supervisor: map.and_then(|m| m.context_of(hart, Mode::Supervisor)),
+        }
+    }
+}
+
+/// A context validated by [`Plic::context_handle`], for the unchecked trap-path operations
+/// [`Plic::claim_fast`] and [`Plic::complete_fast`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ContextHandle(pub(crate) usize);
+
+impl ContextHandle {
+    /// Returns the context index.
+    #[inline]
+    pub const fn get(self) -> usize {
+        self.0
+    }
+}
+
+/// A set of contexts `0..128`, e.g. the contexts a source is routed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ContextMask(u128);
+
+impl ContextMask {
+    /// Number of contexts a mask can hold.
+    pub const CAPACITY: usize = u128::BITS as usize;
+
+    /// The empty set.
+    #[inline]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The set containing only `ctx`.
+    #[inline]
+    pub const fn single(ctx: usize) -> Self {
+        Self::empty().with(ctx)
+    }
+
+    /// Returns `self` with `ctx` added.
+    #[inline]
+    pub const fn with(self, ctx: usize) -> Self {
+        assert!(ctx < Self::CAPACITY);
+        Self(self.0 | 1 << ctx)
+    }
+
+    /// Returns `self` with `ctx` removed.
+    #[inline]
+    pub const fn without(self, ctx: usize) -> Self {
+        assert!(ctx < Self::CAPACITY);
+        Self(self.0 & !(1 << ctx))
+    }
+
+    /// Returns whether `ctx` is in the set.
+    #[inline]
+    pub const fn contains(self, ctx: usize) -> bool {
+        ctx < Self::CAPACITY && self.0 & (1 << ctx) != 0
+    }
+
+    /// Returns whether the set is empty.
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterates over the contexts in the set, in ascending order.
+    pub fn iter(self) -> impl Iterator<Item = usize> {
+        let mut bits = self.0;
+        core::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+            let ctx = bits.trailing_zeros() as usize;
+            bits &= bits - 1;
+            Some(ctx)
+        })
+    }
+}
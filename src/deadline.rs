@@ -0,0 +1,89 @@
+//! Completion-deadline monitoring.
+//!
+//! A [`DeadlineMonitor`] installed as the dispatcher's observer remembers when each source was
+//! claimed. If a source with a deadline is completed more than its deadline after the claim,
+//! or [`DeadlineMonitor::check`] finds it still outstanding past the deadline, the callback is
+//! invoked with the source, the context and the elapsed ticks. This finds handlers that
+//! occasionally blow their budget without the cost of full tracing.
+
+use core::num::NonZeroU32;
+use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering};
+
+use crate::dispatch::IrqObserver;
+
+/// Called with the offending source, its context and the elapsed ticks.
+pub type DeadlineCallback = fn(NonZeroU32, usize, u32);
+
+/// Per-source completion deadlines for sources `0..S`.
+///
+/// Timestamps are truncated to 32 bits, so deadlines must stay below `2^31` clock ticks.
+pub struct DeadlineMonitor<const S: usize> {
+    callback: DeadlineCallback,
+    /// Deadline in ticks, 0 for none.
+    deadline: [AtomicU32; S],
+    claimed_at: [AtomicU32; S],
+    ctx: [AtomicU16; S],
+    outstanding: [AtomicBool; S],
+}
+
+impl<const S: usize> DeadlineMonitor<S> {
+    /// Creates a monitor with no deadlines, reporting overruns to `callback`.
+    pub const fn new(callback: DeadlineCallback) -> Self {
+        Self {
+            callback,
+            deadline: [const { AtomicU32::new(0) }; S],
+            claimed_at: [const { AtomicU32::new(0) }; S],
+            ctx: [const { AtomicU16::new(0) }; S],
+            outstanding: [const { AtomicBool::new(false) }; S],
+        }
+    }
+
+    /// Sets the deadline of interrupt `source` to `ticks` after its claim, or removes it with
+    /// `None`.
+    pub fn set_deadline(&self, source: NonZeroU32, ticks: Option<NonZeroU32>) {
+        self.deadline[source.get() as usize].store(ticks.map_or(0, |t| t.get()), Ordering::Relaxed);
+    }
+
+    /// Reports every source still outstanding past its deadline at time `now`.
+    ///
+    /// Each overrun is reported once, either here or at completion.
+    pub fn check(&self, now: u64) {
+        for (i, outstanding) in self.outstanding.iter().enumerate() {
+            if outstanding.load(Ordering::Acquire) {
+                self.report_if_late(i, now as u32, outstanding);
+            }
+        }
+    }
+
+    fn report_if_late(&self, i: usize, now: u32, outstanding: &AtomicBool) {
+        let deadline = self.deadline[i].load(Ordering::Relaxed);
+        let elapsed = now.wrapping_sub(self.claimed_at[i].load(Ordering::Relaxed));
+        if deadline != 0
+            && elapsed > deadline
+            && outstanding.swap(false, Ordering::AcqRel)
+            && let Some(source) = NonZeroU32::new(i as u32)
+        {
+            let ctx = self.ctx[i].load(Ordering::Relaxed) as usize;
+            (self.callback)(source, ctx, elapsed);
+        }
+    }
+}
+
+impl<const S: usize> IrqObserver for DeadlineMonitor<S> {
+    fn claimed(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        let i = source.get() as usize;
+        if i < S {
+            self.claimed_at[i].store(now as u32, Ordering::Relaxed);
+            self.ctx[i].store(ctx as u16, Ordering::Relaxed);
+            self.outstanding[i].store(true, Ordering::Release);
+        }
+    }
+
+    fn completed(&self, source: NonZeroU32, _ctx: usize, now: u64) {
+        let i = source.get() as usize;
+        if i < S {
+            self.report_if_late(i, now as u32, &self.outstanding[i]);
+            self.outstanding[i].store(false, Ordering::Release);
+        }
+    }
+}
@@ -0,0 +1,436 @@
+//! Interrupt lookup in a flattened device tree.
+//!
+//! Drivers can ask for "the UART0 interrupt" by device-tree node instead of hard-coding source
+//! numbers. Only the `interrupts` and `interrupt-parent` properties are understood, which is
+//! what every PLIC binding uses; `interrupts-extended` is not.
+//!
+//! [`Plic::from_fdt_node`] reads the PLIC node itself, including the context layout given by
+//! its `interrupts-extended` property.
+
+use core::num::NonZeroU32;
+
+use fdt::Fdt;
+use fdt::node::FdtNode;
+
+use crate::context::{ContextMap, ContextTarget};
+use crate::platform::{Platform, machine};
+use crate::{Plic, SOURCE_NUM};
+
+/// `compatible` strings identifying a PLIC node.
+pub const COMPATIBLE: &[&str] = &[
+    "riscv,plic0",
+    "sifive,plic-1.0.0",
+    "thead,c900-plic",
+    "andestech,nceplic100",
+];
+
+/// A device-tree node, by path or by phandle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NodeRef<'a> {
+    /// A node path such as `/soc/serial@10000000`, or an alias.
+    Path(&'a str),
+    /// The `phandle` of a node.
+    Phandle(u32),
+}
+
+/// Returns whether `node` is a PLIC, see [`COMPATIBLE`].
+pub fn is_plic(node: FdtNode<'_, '_>) -> bool {
+    node.compatible()
+        .is_some_and(|c| c.all().any(|c| COMPATIBLE.contains(&c)))
+}
+
+/// Resolves the first interrupt of `node` to a PLIC source.
+///
+/// The interrupt parent is taken from the node or, for nodes given by path or alias, the
+/// nearest ancestor with an `interrupt-parent` property; for nodes given by phandle only the node and
+/// the root are searched. Returns `None` if the node has no interrupts or they are not routed
+/// to a PLIC.
+pub fn source_for(fdt: &Fdt<'_>, node: NodeRef<'_>) -> Option<NonZeroU32> {
+    let (device, parent) = device_and_parent(fdt, node)?;
+    if !is_plic(parent) {
+        return None;
+    }
+    let cell = device.property("interrupts")?.value.first_chunk::<4>()?;
+    NonZeroU32::new(u32::from_be_bytes(*cell))
+}
+
+/// Finds `node` and its interrupt parent, see [`source_for`].
+fn device_and_parent<'b, 'a>(
+    fdt: &'b Fdt<'a>,
+    node: NodeRef<'_>,
+) -> Option<(FdtNode<'b, 'a>, FdtNode<'b, 'a>)> {
+    let (device, path) = match node {
+        NodeRef::Path(path) => {
+            // Walk the parents of the node an alias stands for, not of the alias.
+            let path = if path.starts_with('/') {
+                path
+            } else {
+                fdt.aliases()?.resolve(path)?
+            };
+            (fdt.find_node(path)?, path)
+        }
+        NodeRef::Phandle(phandle) => (fdt.find_phandle(phandle)?, "/"),
+    };
+    let parent = device
+        .interrupt_parent()
+        .or_else(|| inherited_interrupt_parent(fdt, path))?;
+    Some((device, parent))
+}
+
+/// Searches the ancestors of the node at `path` for an `interrupt-parent` property.
+fn inherited_interrupt_parent<'b, 'a>(fdt: &'b Fdt<'a>, mut path: &str) -> Option<FdtNode<'b, 'a>> {
+    loop {
+        path = match path.rfind('/')? {
+            0 => "/",
+            i => &path[..i],
+        };
+        if let Some(parent) = fdt.find_node(path).and_then(|n| n.interrupt_parent()) {
+            return Some(parent);
+        }
+        if path == "/" {
+            return None;
+        }
+    }
+}
+
+/// The trigger type of a two-cell interrupt specifier, in the encoding of the generic
+/// interrupt bindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TriggerHint {
+    /// One-cell specifier, or a trigger type of 0.
+    Unspecified,
+    /// `IRQ_TYPE_EDGE_RISING`.
+    EdgeRising,
+    /// `IRQ_TYPE_EDGE_FALLING`.
+    EdgeFalling,
+    /// `IRQ_TYPE_EDGE_BOTH`.
+    EdgeBoth,
+    /// `IRQ_TYPE_LEVEL_HIGH`.
+    LevelHigh,
+    /// `IRQ_TYPE_LEVEL_LOW`.
+    LevelLow,
+}
+
+impl TriggerHint {
+    /// Decodes the trigger type cell of an interrupt specifier.
+    pub const fn from_cell(cell: u32) -> Self {
+        match cell & 0xf {
+            1 => Self::EdgeRising,
+            2 => Self::EdgeFalling,
+            3 => Self::EdgeBoth,
+            4 => Self::LevelHigh,
+            8 => Self::LevelLow,
+            _ => Self::Unspecified,
+        }
+    }
+
+    /// Returns whether the hint describes an edge-triggered interrupt.
+    pub const fn is_edge(self) -> bool {
+        matches!(self, Self::EdgeRising | Self::EdgeFalling | Self::EdgeBoth)
+    }
+}
+
+/// Decodes an `interrupts` property value made of `cells`-cell specifiers into source numbers
+/// and trigger hints.
+///
+/// The first cell is the source and the second, if any, the trigger type; further cells are
+/// ignored. Specifiers naming source 0 and a truncated trailing specifier are skipped.
+pub fn decode_interrupts(
+    value: &[u8],
+    cells: usize,
+) -> impl Iterator<Item = (NonZeroU32, TriggerHint)> + '_ {
+    value
+        .chunks_exact(cells.max(1) * 4)
+        .filter_map(move |spec| {
+            let cell = |i: usize| u32::from_be_bytes(spec[i * 4..][..4].try_into().unwrap());
+            let trigger = match cells {
+                0 | 1 => TriggerHint::Unspecified,
+                _ => TriggerHint::from_cell(cell(1)),
+            };
+            Some((NonZeroU32::new(cell(0))?, trigger))
+        })
+}
+
+/// Decodes the `interrupts` property of a device node, see [`decode_interrupts`].
+///
+/// The specifier size is the `#interrupt-cells` of the interrupt parent, found as in
+/// [`source_for`], so a parent inherited from an ancestor such as `/soc` is honoured. Yields
+/// nothing if no parent or cell count can be found.
+pub fn parse_device_interrupts<'a>(
+    fdt: &Fdt<'a>,
+    node: NodeRef<'_>,
+) -> impl Iterator<Item = (NonZeroU32, TriggerHint)> + 'a {
+    let (value, cells) = device_and_parent(fdt, node)
+        .and_then(|(device, parent)| {
+            Some((
+                device.property("interrupts")?.value,
+                parent.interrupt_cells()?,
+            ))
+        })
+        .unwrap_or((&[], 0));
+    decode_interrupts(value, cells)
+}
+
+/// Exception code of a supervisor external interrupt in a hart's local interrupt controller.
+const SUPERVISOR_EXTERNAL: u32 = 9;
+/// Exception code of a machine external interrupt in a hart's local interrupt controller.
+const MACHINE_EXTERNAL: u32 = 11;
+
+/// The parameters of a PLIC read from its device-tree node, with room for `C` contexts, see
+/// [`Plic::from_fdt_node`].
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PlicInfo<const C: usize> {
+    /// Physical base address of the PLIC.
+    pub base: u64,
+    /// Size of the PLIC MMIO region.
+    pub size: usize,
+    /// Number of implemented interrupt sources (`riscv,ndev`).
+    pub ndev: u32,
+    targets: [Option<ContextTarget>; C],
+    len: usize,
+}
+
+impl<const C: usize> PlicInfo<C> {
+    /// Returns the context layout (`interrupts-extended`).
+    #[inline]
+    pub fn contexts(&self) -> ContextMap<'_> {
+        ContextMap::new(&self.targets[..self.len])
+    }
+
+    /// Returns the parameters as a [`Platform`] called `name` with the
+    /// [`Generic`](crate::variant::Generic) variant, e.g. to bring the PLIC up with
+    /// [`Platform::init`].
+    pub fn platform(&'static self, name: &'static str) -> Platform {
+        Platform {
+            name,
+            base: self.base,
+            size: self.size,
+            ndev: self.ndev,
+            contexts: self.contexts(),
+            variant: &crate::variant::Generic,
+        }
+    }
+}
+
+impl Plic {
+    /// Reads the base address, size, source count and context layout of the PLIC described by
+    /// `node`.
+    ///
+    /// Each `interrupts-extended` entry names the local interrupt controller of a hart under
+    /// `/cpus` and the external interrupt of one mode; entries naming anything else are
+    /// unconnected contexts. With the `supervisor-only` feature machine contexts are listed
+    /// as unconnected, as in the presets. Returns `None` if `node` is not a PLIC, lacks `reg`
+    /// or `riscv,ndev`, claims 1024 or more sources, or has more than `C` contexts.
+    #[cold]
+    pub fn from_fdt_node<const C: usize>(
+        fdt: &Fdt<'_>,
+        node: FdtNode<'_, '_>,
+    ) -> Option<PlicInfo<C>> {
+        if !is_plic(node) {
+            return None;
+        }
+        let region = node.reg()?.next()?;
+        let ndev = node.property("riscv,ndev")?.as_usize()?;
+        if ndev >= SOURCE_NUM {
+            return None;
+        }
+        let mut info = PlicInfo {
+            base: region.starting_address as u64,
+            size: region.size?,
+            ndev: ndev as u32,
+            targets: [None; C],
+            len: 0,
+        };
+        let value = node
+            .property("interrupts-extended")
+            .map_or(&[][..], |p| p.value);
+        for entry in value.chunks_exact(8) {
+            let cell = |i: usize| u32::from_be_bytes(entry[i * 4..][..4].try_into().unwrap());
+            let target = hart_of(fdt, cell(0)).and_then(|hart| match cell(1) {
+                SUPERVISOR_EXTERNAL => Some(ContextTarget::supervisor(hart)),
+                MACHINE_EXTERNAL => machine(hart),
+                _ => None,
+            });
+            *info.targets.get_mut(info.len)? = target;
+            info.len += 1;
+        }
+        Some(info)
+    }
+}
+
+/// Returns the hart whose local interrupt controller has `phandle`.
+fn hart_of(fdt: &Fdt<'_>, phandle: u32) -> Option<usize> {
+    fdt.find_node("/cpus")?.children().find_map(|cpu| {
+        cpu.children()
+            .any(|intc| {
+                intc.property("phandle").and_then(|p| p.as_usize()) == Some(phandle as usize)
+            })
+            .then(|| cpu.property("reg")?.as_usize())
+            .flatten()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Serializes a flattened device tree, see the devicetree specification, §5.
+    #[derive(Default)]
+    struct Builder {
+        structs: Vec<u8>,
+        strings: Vec<u8>,
+    }
+
+    impl Builder {
+        fn token(&mut self, token: u32) -> &mut Self {
+            self.structs.extend(token.to_be_bytes());
+            self
+        }
+
+        fn pad(&mut self) {
+            while !self.structs.len().is_multiple_of(4) {
+                self.structs.push(0);
+            }
+        }
+
+        fn begin(&mut self, name: &str) -> &mut Self {
+            self.token(1);
+            self.structs.extend(name.as_bytes());
+            self.structs.push(0);
+            self.pad();
+            self
+        }
+
+        fn end(&mut self) -> &mut Self {
+            self.token(2)
+        }
+
+        fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+            let offset = self.strings.len() as u32;
+            self.strings.extend(name.as_bytes());
+            self.strings.push(0);
+            self.token(3).token(value.len() as u32).token(offset);
+            self.structs.extend(value);
+            self.pad();
+            self
+        }
+
+        fn cells(&mut self, name: &str, cells: &[u32]) -> &mut Self {
+            let value: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+            self.prop(name, &value)
+        }
+
+        fn string(&mut self, name: &str, value: &str) -> &mut Self {
+            self.prop(name, &[value.as_bytes(), &[0]].concat())
+        }
+
+        fn finish(&mut self) -> Vec<u8> {
+            self.token(9);
+            let structs = 40 + 16;
+            let strings = structs + self.structs.len();
+            let header = [
+                0xd00d_feed,
+                (strings + self.strings.len()) as u32,
+                structs as u32,
+                strings as u32,
+                40,
+                17,
+                16,
+                0,
+                self.strings.len() as u32,
+                self.structs.len() as u32,
+            ];
+            let mut blob: Vec<u8> = header.iter().flat_map(|w| w.to_be_bytes()).collect();
+            blob.extend([0; 16]);
+            blob.extend(&self.structs);
+            blob.extend(&self.strings);
+            blob
+        }
+    }
+
+    /// A single hart whose machine and supervisor external interrupts are contexts 0 and 1 of
+    /// a PLIC with `ndev` sources, and a UART under `/soc` inheriting it as interrupt parent,
+    /// also known as `serial0`.
+    fn tree(ndev: u32) -> Vec<u8> {
+        Builder::default()
+            .begin("")
+            .cells("#address-cells", &[1])
+            .cells("#size-cells", &[1])
+            .begin("cpus")
+            .cells("#address-cells", &[1])
+            .cells("#size-cells", &[0])
+            .begin("cpu@0")
+            .cells("reg", &[0])
+            .begin("interrupt-controller")
+            .cells("phandle", &[1])
+            .end()
+            .end()
+            .end()
+            .begin("plic@c000000")
+            .string("compatible", "riscv,plic0")
+            .cells("reg", &[0xc00_0000, 0x400_0000])
+            .cells("riscv,ndev", &[ndev])
+            .cells(
+                "interrupts-extended",
+                &[1, MACHINE_EXTERNAL, 1, SUPERVISOR_EXTERNAL],
+            )
+            .cells("#interrupt-cells", &[2])
+            .cells("phandle", &[2])
+            .end()
+            .begin("aliases")
+            .string("serial0", "/soc/serial@10000000")
+            .end()
+            .begin("soc")
+            .cells("interrupt-parent", &[2])
+            .begin("serial@10000000")
+            .cells("interrupts", &[10, 4, 11, 1])
+            .end()
+            .end()
+            .end()
+            .finish()
+    }
+
+    fn plic_info(blob: &[u8]) -> Option<PlicInfo<4>> {
+        let fdt = Fdt::new(blob).unwrap();
+        Plic::from_fdt_node(&fdt, fdt.find_node("/plic@c000000").unwrap())
+    }
+
+    #[test]
+    fn from_fdt_node_reads_the_plic_node() {
+        let info = plic_info(&tree(1023)).unwrap();
+        assert_eq!(
+            (info.base, info.size, info.ndev),
+            (0xc00_0000, 0x400_0000, 1023)
+        );
+        let contexts = info.contexts();
+        assert_eq!(contexts.len(), 2);
+        assert_eq!(contexts.target(0), machine(0));
+        assert_eq!(contexts.target(1), Some(ContextTarget::supervisor(0)));
+    }
+
+    #[test]
+    fn from_fdt_node_rejects_too_many_sources() {
+        assert!(plic_info(&tree(1024)).is_none());
+    }
+
+    #[test]
+    fn interrupts_of_an_alias_use_the_parent_of_its_node() {
+        let blob = tree(1023);
+        let fdt = Fdt::new(&blob).unwrap();
+        let expected = [
+            (NonZeroU32::new(10).unwrap(), TriggerHint::LevelHigh),
+            (NonZeroU32::new(11).unwrap(), TriggerHint::EdgeRising),
+        ];
+        for node in ["/soc/serial@10000000", "serial0"].map(NodeRef::Path) {
+            assert!(parse_device_interrupts(&fdt, node).eq(expected));
+            assert_eq!(source_for(&fdt, node), Some(expected[0].0));
+        }
+    }
+}
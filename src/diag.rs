@@ -0,0 +1,257 @@
+//! Diagnostics for interrupt delivery problems.
+
+use core::fmt;
+use core::num::NonZeroU32;
+
+use tock_registers::interfaces::Readable;
+
+#[cfg(all(feature = "csr", any(target_arch = "riscv32", target_arch = "riscv64")))]
+use crate::context::Mode;
+use crate::context::{ContextMap, ContextTarget};
+use crate::{EnableSnapshot, Plic, U32_BITS};
+
+/// Reads the CSR named `$csr`.
+#[cfg(all(feature = "csr", any(target_arch = "riscv32", target_arch = "riscv64")))]
+macro_rules! read_csr {
+    ($csr:literal) => {{
+        let value: usize;
+        unsafe { core::arch::asm!(concat!("csrr {0}, ", $csr), out(reg) value, options(nomem, nostack)) };
+        value
+    }};
+}
+
+/// The state of one source in one context, see [`Plic::iter_sources`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SourceInfo<'n> {
+    /// The interrupt source.
+    pub source: NonZeroU32,
+    /// The device name, if known.
+    pub name: Option<&'n str>,
+    /// Priority of the source.
+    pub priority: u32,
+    /// Whether the source is pending.
+    pub pending: bool,
+    /// Whether the source is enabled in the context.
+    pub enabled: bool,
+    /// Whether the source is claimed and not yet completed, see [`Plic::is_in_service`].
+    pub in_service: bool,
+}
+
+/// The routing of one context, see [`Plic::routing_report`].
+///
+/// The threshold and enables are captured when the entry is produced; priorities are read
+/// when iterating [`ContextRoute::sources`].
+pub struct ContextRoute<'p> {
+    plic: &'p Plic,
+    /// The context.
+    pub context: usize,
+    /// The hart and mode it delivers to.
+    pub target: ContextTarget,
+    /// Its threshold.
+    pub threshold: u32,
+    /// Its enable bits.
+    pub enables: EnableSnapshot,
+}
+
+impl ContextRoute<'_> {
+    /// Iterates over the implemented sources enabled in the context with their priorities,
+    /// in ascending order.
+    pub fn sources(&self) -> impl Iterator<Item = (NonZeroU32, u32)> + '_ {
+        (1..=self.plic.ndev)
+            .filter_map(NonZeroU32::new)
+            .filter(|&source| self.enables.is_enabled(source))
+            .map(|source| (source, self.plic.get_priority(source)))
+    }
+}
+
+/// Why interrupt `source` is (or is not) delivered to a context, see [`Plic::diagnose`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Diagnosis {
+    /// The source priority is 0, which the PLIC treats as "never interrupt".
+    PriorityZero,
+    /// The source is not enabled in the context.
+    NotEnabled,
+    /// The source priority does not exceed the context threshold.
+    BelowThreshold {
+        /// Priority of the source.
+        priority: u32,
+        /// Threshold of the context.
+        threshold: u32,
+    },
+    /// The source was claimed on this context and is waiting for completion.
+    InService,
+    /// The source was claimed on another context and is waiting for completion there.
+    ClaimedElsewhere {
+        /// The context holding the claim.
+        context: usize,
+    },
+    /// Routing is fine, but the gateway has not latched a request from the device.
+    NotPending,
+    /// The source is pending and should be claimable from the context.
+    Deliverable,
+}
+
+impl Plic {
+    /// Explains why interrupt `source` is or is not delivered to `context`.
+    ///
+    /// The checks are performed in order of configuration first (priority, enable, threshold),
+    /// then claim state, then the pending bit, and the first failing one is reported.
+    #[cold]
+    pub fn diagnose(&self, source: NonZeroU32, ctx: usize) -> Diagnosis {
+        let priority = self.get_priority(source);
+        if priority == 0 {
+            return Diagnosis::PriorityZero;
+        }
+        if !self.is_enabled(source, ctx) {
+            return Diagnosis::NotEnabled;
+        }
+        let threshold = self.get_threshold(ctx);
+        if priority <= threshold {
+            return Diagnosis::BelowThreshold {
+                priority,
+                threshold,
+            };
+        }
+        match self.in_service_context(source) {
+            Some(context) if context == ctx => return Diagnosis::InService,
+            Some(context) => return Diagnosis::ClaimedElsewhere { context },
+            None => {}
+        }
+        if self.is_pending(source) {
+            Diagnosis::Deliverable
+        } else {
+            Diagnosis::NotPending
+        }
+    }
+
+    /// Iterates over the implemented sources, see [`Plic::set_ndev`], with their state in
+    /// `context`, in ascending order.
+    ///
+    /// Pending and enable bits are read one word per 32 sources, so a full listing costs one
+    /// pass over the register space. `name` supplies the device names, if any.
+    pub fn iter_sources<'a, 'n: 'a>(
+        &'a self,
+        ctx: usize,
+        name: impl Fn(NonZeroU32) -> Option<&'n str> + 'a,
+    ) -> impl Iterator<Item = SourceInfo<'n>> + 'a {
+        let enables = self.enable_regs(ctx);
+        let mut words = (usize::MAX, 0, 0);
+        self.sources().map(move |source| {
+            let (group, bit) = (
+                source.get() as usize / U32_BITS,
+                source.get() as usize % U32_BITS,
+            );
+            if words.0 != group {
+                words = (
+                    group,
+                    self.regs().interrupt_pending[group].get(),
+                    enables[group].get(),
+                );
+            }
+            SourceInfo {
+                source,
+                name: name(source),
+                priority: self.get_priority(source),
+                pending: words.1 >> bit & 1 != 0,
+                enabled: words.2 >> bit & 1 != 0,
+                in_service: self.is_in_service(source),
+            }
+        })
+    }
+
+    /// Reports the routing of every connected context of `map`, per hart and mode, so
+    /// orchestration layers can check it against their policy after boot.
+    pub fn routing_report<'p>(
+        &'p self,
+        map: &ContextMap<'p>,
+    ) -> impl Iterator<Item = ContextRoute<'p>> + 'p {
+        map.contexts().map(|(context, target)| ContextRoute {
+            plic: self,
+            context,
+            target,
+            threshold: self.get_threshold(context),
+            enables: self.save_enables(context),
+        })
+    }
+
+    /// Writes a human-readable summary of `context` to `out`: its threshold, followed by one
+    /// line per enabled source with its priority, pending/in-service state and the name
+    /// returned by `name` (if any).
+    ///
+    /// Lines stay within 80 columns as long as names do not exceed 40 characters, so the output
+    /// is suitable for a serial console or a debug-shell command.
+    #[cold]
+    pub fn dump_context<'a>(
+        &self,
+        out: &mut dyn fmt::Write,
+        ctx: usize,
+        name: impl Fn(NonZeroU32) -> Option<&'a str>,
+    ) -> fmt::Result {
+        writeln!(out, "context {ctx}: threshold {}", self.get_threshold(ctx))?;
+        for info in self.iter_sources(ctx, name).filter(|i| i.enabled) {
+            writeln!(
+                out,
+                "  irq {:4}  prio {:3}  {}{}  {}",
+                info.source,
+                info.priority,
+                if info.pending { 'P' } else { '-' },
+                if info.in_service { 'S' } else { '-' },
+                info.name.unwrap_or(""),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The external-interrupt pending bit of a hart compared with the PLIC state of its context,
+/// see [`Plic::check_eip_consistency`].
+#[cfg(all(feature = "csr", any(target_arch = "riscv32", target_arch = "riscv64")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EipReport {
+    /// `sip.SEIP` or `mip.MEIP` of the current hart.
+    pub eip: bool,
+    /// The highest-priority source the PLIC should be signalling, if any.
+    pub deliverable: Option<NonZeroU32>,
+}
+
+#[cfg(all(feature = "csr", any(target_arch = "riscv32", target_arch = "riscv64")))]
+impl EipReport {
+    /// Returns whether the pending bit agrees with the PLIC.
+    ///
+    /// A mismatch is either the classic "EIP set but claim returns 0", or "pending but EIP
+    /// clear", usually a wrong context-to-hart mapping.
+    pub const fn is_consistent(&self) -> bool {
+        self.eip == self.deliverable.is_some()
+    }
+}
+
+#[cfg(all(feature = "csr", any(target_arch = "riscv32", target_arch = "riscv64")))]
+impl Plic {
+    /// Compares the external-interrupt pending bit of the current hart for `mode` with what
+    /// the PLIC state of `context` implies, for bring-up.
+    ///
+    /// `context` must be the `mode` context of the calling hart. Reading `mip` requires machine
+    /// mode; with the `supervisor-only` feature it is never read and `mip.MEIP` reports as
+    /// clear. Sources change state asynchronously, so run this with external interrupts masked
+    /// and repeat it before trusting a mismatch.
+    #[cold]
+    pub fn check_eip_consistency(&self, ctx: usize, mode: Mode) -> EipReport {
+        let eip = match mode {
+            #[cfg(not(feature = "supervisor-only"))]
+            Mode::Machine => read_csr!("mip") >> 11 & 1 != 0,
+            #[cfg(feature = "supervisor-only")]
+            Mode::Machine => false,
+            Mode::Supervisor => read_csr!("sip") >> 9 & 1 != 0,
+        };
+        let threshold = self.get_threshold(ctx);
+        let deliverable = self
+            .iter_sources(ctx, |_| None)
+            .filter(|i| i.pending && i.enabled && i.priority > threshold)
+            .max_by_key(|i| (i.priority, core::cmp::Reverse(i.source)))
+            .map(|i| i.source);
+        EipReport { eip, deliverable }
+    }
+}
@@ -0,0 +1,1042 @@
+//! Interrupt handler registration and dispatch.
+
+use core::num::NonZeroU32;
+use core::ops::{BitOr, BitOrAssign};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::context::ContextMask;
+use crate::{Plic, PlicError, Priority};
+
+/// An interrupt handler, called with the source it was registered for.
+pub type Handler = fn(NonZeroU32) -> IrqReturn;
+
+/// What a [`Handler`] did with the interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IrqReturn {
+    /// The interrupt was fully handled.
+    Handled,
+    /// The interrupt did not come from this handler's device; only meaningful for
+    /// [`IrqFlags::SHARED`] sources.
+    Unhandled,
+    /// Handling was deferred, e.g. to a thread; the dispatcher must not complete a
+    /// [`IrqFlags::ONESHOT`] source until [`Dispatcher::finish_oneshot`] is called.
+    Deferred,
+}
+
+/// Flags controlling how the [`Dispatcher`] handles a source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IrqFlags(u32);
+
+impl IrqFlags {
+    /// Keep the source disabled in the claiming context from claim until its handler finishes,
+    /// including deferred handling. Required for level-triggered lines handled outside IRQ
+    /// context.
+    pub const ONESHOT: Self = Self(1 << 0);
+    /// Allow further handlers on the same source, provided they are shared too. Every handler
+    /// of a shared source is called when it fires.
+    pub const SHARED: Self = Self(1 << 1);
+    /// The handler always finishes in interrupt context; a returned [`IrqReturn::Deferred`]
+    /// is treated as [`IrqReturn::Handled`].
+    pub const NO_THREAD: Self = Self(1 << 2);
+    /// Mark the source as a wakeup source while it is requested, see [`Plic::set_wake`].
+    pub const WAKE: Self = Self(1 << 3);
+    /// Pin the source to the contexts it was requested on, excluding it from load balancing.
+    pub const NO_BALANCE: Self = Self(1 << 4);
+
+    /// No flags set.
+    #[inline]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns whether all flags in `other` are set in `self`.
+    #[inline]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for IrqFlags {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for IrqFlags {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+struct Action {
+    source: NonZeroU32,
+    handler: Handler,
+    flags: IrqFlags,
+    name: &'static str,
+    /// Set by [`Dispatcher::disable_lazy`].
+    disabled: AtomicBool,
+    /// Set while the observer throttles the source, see [`Dispatcher::unthrottle`].
+    throttled: AtomicBool,
+    /// Priority saved when a lazily disabled or throttled source was masked, 0 while unmasked.
+    masked_priority: AtomicU32,
+    /// Tells this registration apart from later ones in the same slot, see [`IrqHandle`].
+    generation: u32,
+    /// Priority the source had before it was first requested, restored by [`IrqHandle::free`].
+    saved_priority: u32,
+    /// Whether freeing the last handler clears the wakeup mark, i.e. a request set it.
+    clear_wake: bool,
+}
+
+/// Source of [`Action::generation`], shared by all dispatchers.
+static NEXT_GENERATION: AtomicU32 = AtomicU32::new(0);
+
+impl Action {
+    /// Masks the source on all contexts by dropping its priority to 0, which is a single
+    /// write instead of an enable-word read-modify-write per context.
+    fn mask(&self, plic: &mut Plic) {
+        let priority = plic.get_priority(self.source);
+        if priority != 0 {
+            self.masked_priority.store(priority, Ordering::Release);
+            plic.set_priority(self.source, 0);
+        }
+    }
+}
+
+/// A fixed-capacity table of up to `N` interrupt handlers.
+///
+/// Handlers of a [`IrqFlags::SHARED`] source each take a slot. Lookups scan the table
+/// linearly, which is cheap compared to the MMIO accesses of a claim for the handful of
+/// handlers a typical system registers.
+pub struct Dispatcher<const N: usize> {
+    actions: [Option<Action>; N],
+    clock: fn() -> u64,
+    observer: Option<&'static dyn IrqObserver>,
+}
+
+/// Receives claim and completion events from a [`Dispatcher`], e.g.
+/// [`IrqStats`](crate::stats::IrqStats).
+///
+/// Timestamps come from the clock set with [`Dispatcher::set_clock`].
+pub trait IrqObserver: Sync {
+    /// Interrupt `source` was claimed in `context` at time `now`.
+    fn claimed(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        let _ = (source, ctx, now);
+    }
+
+    /// Interrupt `source` was completed in `context` at time `now`.
+    fn completed(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        let _ = (source, ctx, now);
+    }
+
+    /// Asked right after [`IrqObserver::claimed`]; returning `true` makes the dispatcher
+    /// mask `source` until [`Dispatcher::unthrottle`] and complete it without calling its
+    /// handlers. A source without handlers is only completed.
+    fn throttle(&self, source: NonZeroU32, ctx: usize, now: u64) -> bool {
+        let _ = (source, ctx, now);
+        false
+    }
+
+    /// Interrupt `source`, which [`IrqObserver::throttle`] asked to throttle in `context`, was
+    /// masked and completed at time `now`.
+    fn throttled(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        let _ = (source, ctx, now);
+    }
+
+    /// A handler of interrupt `source` claimed in `context` panicked at time `now`; the
+    /// source was lazily disabled and completed, see [`Dispatcher::handle_one`].
+    fn panicked(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        let _ = (source, ctx, now);
+    }
+}
+
+/// An observer calling a function with `(source, context)` right after each claim, before
+/// the handlers run.
+///
+/// Meant for kernels that boost the task bound to the device, or attribute wakeups for power
+/// accounting. Combine it with other observers as a pair.
+#[derive(Clone, Copy)]
+pub struct ClaimHook(pub fn(NonZeroU32, usize));
+
+impl IrqObserver for ClaimHook {
+    fn claimed(&self, source: NonZeroU32, ctx: usize, _now: u64) {
+        (self.0)(source, ctx);
+    }
+}
+
+impl<T: IrqObserver + ?Sized> IrqObserver for &T {
+    fn claimed(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        (**self).claimed(source, ctx, now);
+    }
+
+    fn completed(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        (**self).completed(source, ctx, now);
+    }
+
+    fn throttle(&self, source: NonZeroU32, ctx: usize, now: u64) -> bool {
+        (**self).throttle(source, ctx, now)
+    }
+
+    fn throttled(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        (**self).throttled(source, ctx, now);
+    }
+
+    fn panicked(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        (**self).panicked(source, ctx, now);
+    }
+}
+
+/// Combines two observers; both see every event, and a source is throttled if either asks.
+impl<A: IrqObserver, B: IrqObserver> IrqObserver for (A, B) {
+    fn claimed(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        self.0.claimed(source, ctx, now);
+        self.1.claimed(source, ctx, now);
+    }
+
+    fn completed(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        self.0.completed(source, ctx, now);
+        self.1.completed(source, ctx, now);
+    }
+
+    fn throttle(&self, source: NonZeroU32, ctx: usize, now: u64) -> bool {
+        // Both must be asked, so that each can track its own state.
+        let first = self.0.throttle(source, ctx, now);
+        self.1.throttle(source, ctx, now) || first
+    }
+
+    fn throttled(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        self.0.throttled(source, ctx, now);
+        self.1.throttled(source, ctx, now);
+    }
+
+    fn panicked(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        self.0.panicked(source, ctx, now);
+        self.1.panicked(source, ctx, now);
+    }
+}
+
+impl<const N: usize> Dispatcher<N> {
+    /// Creates an empty dispatcher.
+    pub const fn new() -> Self {
+        Self {
+            actions: [const { None }; N],
+            clock: || 0,
+            observer: None,
+        }
+    }
+
+    /// Sets the timestamp source passed to the observer, e.g. a `time` CSR read.
+    ///
+    /// Without a clock, all timestamps are 0.
+    pub fn set_clock(&mut self, clock: fn() -> u64) {
+        self.clock = clock;
+    }
+
+    /// Sets the observer notified of every claim and completion, or removes it.
+    pub fn set_observer(&mut self, observer: Option<&'static dyn IrqObserver>) {
+        self.observer = observer;
+    }
+
+    /// Registers `handler` for interrupt `source`.
+    ///
+    /// Fails with [`PlicError::SourceBusy`] if `source` already has a handler and either of
+    /// them is not [`IrqFlags::SHARED`], or with [`PlicError::NoSpace`] if the table is full.
+    #[cold]
+    pub fn register(&mut self, source: NonZeroU32, handler: Handler) -> Result<(), PlicError> {
+        self.register_with_flags(source, handler, IrqFlags::empty())
+    }
+
+    /// Registers `handler` for interrupt `source` with `flags`, see [`Dispatcher::register`].
+    #[cold]
+    pub fn register_with_flags(
+        &mut self,
+        source: NonZeroU32,
+        handler: Handler,
+        flags: IrqFlags,
+    ) -> Result<(), PlicError> {
+        self.insert(source, handler, flags, "").map(drop)
+    }
+
+    fn insert(
+        &mut self,
+        source: NonZeroU32,
+        handler: Handler,
+        flags: IrqFlags,
+        name: &'static str,
+    ) -> Result<(usize, u32), PlicError> {
+        let shared = flags.contains(IrqFlags::SHARED);
+        let (mut disabled, mut throttled) = (false, false);
+        let (mut saved_priority, mut clear_wake) = (0, false);
+        for action in self.actions_of(source) {
+            if !shared || !action.flags.contains(IrqFlags::SHARED) {
+                return Err(PlicError::SourceBusy {
+                    source: source.get(),
+                });
+            }
+            disabled |= action.disabled.load(Ordering::Acquire);
+            throttled |= action.throttled.load(Ordering::Acquire);
+            saved_priority = action.saved_priority;
+            clear_wake |= action.clear_wake;
+        }
+        let slot = self
+            .actions
+            .iter()
+            .position(|a| a.is_none())
+            .ok_or(PlicError::NoSpace)?;
+        let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+        self.actions[slot] = Some(Action {
+            source,
+            handler,
+            flags,
+            name,
+            disabled: AtomicBool::new(disabled),
+            throttled: AtomicBool::new(throttled),
+            masked_priority: AtomicU32::new(0),
+            generation,
+            saved_priority,
+            clear_wake,
+        });
+        Ok((slot, generation))
+    }
+
+    /// Removes the first handler registered for interrupt `source`, returning it.
+    #[cold]
+    pub fn unregister(&mut self, source: NonZeroU32) -> Option<Handler> {
+        let slot = self
+            .actions
+            .iter_mut()
+            .find(|a| a.as_ref().is_some_and(|a| a.source == source))?;
+        slot.take().map(|a| a.handler)
+    }
+
+    /// Returns the first handler registered for interrupt `source`.
+    #[inline]
+    pub fn handler(&self, source: NonZeroU32) -> Option<Handler> {
+        self.action(source).map(|a| a.handler)
+    }
+
+    /// Returns the flags the first handler of interrupt `source` was registered with.
+    #[inline]
+    pub fn flags(&self, source: NonZeroU32) -> Option<IrqFlags> {
+        self.action(source).map(|a| a.flags)
+    }
+
+    /// Returns the name the first handler of interrupt `source` was requested with, see
+    /// [`Dispatcher::request_irq`].
+    #[inline]
+    pub fn name(&self, source: NonZeroU32) -> Option<&'static str> {
+        self.action(source).map(|a| a.name)
+    }
+
+    /// Requests interrupt `source` for a driver: registers `handler` with `flags` under `name`,
+    /// gives the source the lowest active priority if it has none, and enables it in every
+    /// context of `contexts`. With [`IrqFlags::WAKE`] the source is also marked as a wakeup
+    /// source.
+    ///
+    /// The returned handle undoes all of this with [`IrqHandle::free`]. Fails before
+    /// registering anything with [`PlicError::InvalidContext`] if `contexts` holds a context
+    /// the PLIC does not have, and with [`PlicError::NoState`] for [`IrqFlags::WAKE`] without a
+    /// [`PlicState`](crate::PlicState).
+    #[cold]
+    pub fn request_irq(
+        &mut self,
+        plic: &mut Plic,
+        source: NonZeroU32,
+        handler: Handler,
+        flags: IrqFlags,
+        name: &'static str,
+        contexts: ContextMask,
+    ) -> Result<IrqHandle, PlicError> {
+        for ctx in contexts.iter() {
+            plic.check_context(ctx)?;
+        }
+        let wake = flags.contains(IrqFlags::WAKE);
+        if wake && plic.state.is_none() {
+            return Err(PlicError::NoState);
+        }
+        let first = self.action(source).is_none();
+        let priority = plic.get_priority(source);
+        let set_wake = wake && !plic.is_wake(source);
+        let (slot, generation) = self.insert(source, handler, flags, name)?;
+        if let Some(action) = &mut self.actions[slot] {
+            if first {
+                action.saved_priority = priority;
+            }
+            action.clear_wake |= set_wake;
+        }
+        if priority == 0 {
+            plic.set_priority(source, Priority::MIN_ACTIVE.get());
+        }
+        for ctx in contexts.iter() {
+            plic.enable(source, ctx);
+        }
+        if wake {
+            plic.set_wake(source, true);
+        }
+        Ok(IrqHandle {
+            source,
+            contexts,
+            slot,
+            generation,
+        })
+    }
+
+    #[inline]
+    fn action(&self, source: NonZeroU32) -> Option<&Action> {
+        self.actions_of(source).next()
+    }
+
+    #[inline]
+    fn actions_of(&self, source: NonZeroU32) -> impl Iterator<Item = &Action> {
+        self.actions
+            .iter()
+            .flatten()
+            .filter(move |a| a.source == source)
+    }
+
+    /// Masks `source` for lazy disable unless one of its handlers already did.
+    fn mask(&self, plic: &mut Plic, source: NonZeroU32) {
+        let masked = self
+            .actions_of(source)
+            .any(|a| a.masked_priority.load(Ordering::Acquire) != 0);
+        if !masked && let Some(action) = self.action(source) {
+            action.mask(plic);
+        }
+    }
+
+    /// Disables interrupt `source` without touching the enable registers.
+    ///
+    /// The source is only marked disabled in software. If it is already pending it is masked
+    /// right away; otherwise it is masked the first time it actually fires, and its handler is
+    /// not called. This avoids the MMIO read-modify-write of [`Plic::disable`] for drivers that
+    /// toggle their interrupt frequently.
+    ///
+    /// Masking is done by setting the source priority to 0, so it applies to all contexts.
+    pub fn disable_lazy(&self, plic: &mut Plic, source: NonZeroU32) {
+        for action in self.actions_of(source) {
+            action.disabled.store(true, Ordering::Release);
+        }
+        if self.action(source).is_some() && plic.is_pending(source) {
+            self.mask(plic, source);
+        }
+    }
+
+    /// Re-enables interrupt `source` after [`Dispatcher::disable_lazy`].
+    ///
+    /// Returns whether the source had to be masked in hardware while disabled. A level-triggered
+    /// line that is still asserted is delivered again once its priority is restored; an edge
+    /// that fired while disabled is lost, so the caller may want to poll its device.
+    ///
+    /// A throttled source stays masked until [`Dispatcher::unthrottle`].
+    pub fn enable_lazy(&self, plic: &mut Plic, source: NonZeroU32) -> bool {
+        let mut throttled = false;
+        for action in self.actions_of(source) {
+            action.disabled.store(false, Ordering::Release);
+            throttled |= action.throttled.load(Ordering::Acquire);
+        }
+        !throttled && self.unmask(plic, source)
+    }
+
+    /// Unmasks interrupt `source` after the observer throttled it, see
+    /// [`IrqObserver::throttle`].
+    ///
+    /// Returns whether the priority was restored. A source disabled with
+    /// [`Dispatcher::disable_lazy`] meanwhile stays masked until [`Dispatcher::enable_lazy`].
+    pub fn unthrottle(&self, plic: &mut Plic, source: NonZeroU32) -> bool {
+        let mut disabled = false;
+        for action in self.actions_of(source) {
+            action.throttled.store(false, Ordering::Release);
+            disabled |= action.disabled.load(Ordering::Acquire);
+        }
+        !disabled && self.unmask(plic, source)
+    }
+
+    /// Restores the priority saved when `source` was masked, returning whether there was one.
+    fn unmask(&self, plic: &mut Plic, source: NonZeroU32) -> bool {
+        let mut saved = 0;
+        for action in self.actions_of(source) {
+            saved |= action.masked_priority.swap(0, Ordering::AcqRel);
+        }
+        if saved != 0 {
+            plic.set_priority(source, saved);
+        }
+        saved != 0
+    }
+
+    /// Claims an interrupt in `context`, calls its registered handlers and completes it.
+    ///
+    /// Returns whether an interrupt was claimed. A claimed source without a handler, one
+    /// disabled with [`Dispatcher::disable_lazy`], or one the observer asks to throttle, is
+    /// completed without calling a handler.
+    ///
+    /// A [`IrqFlags::ONESHOT`] source is disabled in `context` before its handlers run. If a
+    /// handler returns [`IrqReturn::Deferred`], the source stays disabled and claimed until
+    /// [`Dispatcher::finish_oneshot`] is called. Whether a source is oneshot is decided by its
+    /// first handler.
+    ///
+    /// If a handler panics and the panic unwinds, the source is lazily disabled, completed and
+    /// reported with [`IrqObserver::panicked`] before the panic propagates, so a level-triggered
+    /// line is not left claimed forever. With `panic = "abort"` nothing runs after the panic.
+    #[inline]
+    pub fn handle_one(&self, plic: &mut Plic, ctx: usize) -> bool {
+        let Some(source) = plic.claim(ctx) else {
+            return false;
+        };
+        let mut throttle = false;
+        if let Some(observer) = self.observer {
+            let now = (self.clock)();
+            observer.claimed(source, ctx, now);
+            throttle = observer.throttle(source, ctx, now);
+        }
+        let Some(action) = self.action(source) else {
+            self.complete(plic, ctx, source);
+            return true;
+        };
+        if throttle {
+            for action in self.actions_of(source) {
+                action.throttled.store(true, Ordering::Release);
+            }
+            self.mask(plic, source);
+            self.complete(plic, ctx, source);
+            if let Some(observer) = self.observer {
+                observer.throttled(source, ctx, (self.clock)());
+            }
+            return true;
+        }
+        if action.disabled.load(Ordering::Acquire) || action.throttled.load(Ordering::Acquire) {
+            self.mask(plic, source);
+            self.complete(plic, ctx, source);
+            return true;
+        }
+        let oneshot = action.flags.contains(IrqFlags::ONESHOT);
+        if oneshot {
+            plic.disable(source, ctx);
+        }
+        let guard = PanicGuard {
+            dispatcher: self,
+            plic: &mut *plic,
+            ctx,
+            source,
+        };
+        let mut deferred = false;
+        for action in self.actions_of(source) {
+            deferred |= (action.handler)(source) == IrqReturn::Deferred
+                && !action.flags.contains(IrqFlags::NO_THREAD);
+        }
+        core::mem::forget(guard);
+        if !oneshot {
+            self.complete(plic, ctx, source);
+        } else if !deferred {
+            self.finish_oneshot(plic, ctx, source);
+        }
+        true
+    }
+
+    /// Handles interrupts in `context` until nothing is left or `budget` is used up.
+    ///
+    /// Draining several claims per trap amortizes trap entry and exit for high-rate devices.
+    /// Returns the number of interrupts claimed; the time budget is measured with the clock
+    /// set by [`Dispatcher::set_clock`].
+    #[inline]
+    pub fn drain(&self, plic: &mut Plic, ctx: usize, budget: Budget) -> u32 {
+        let start = (self.clock)();
+        let mut claimed = 0;
+        while claimed < budget.claims && self.handle_one(plic, ctx) {
+            claimed += 1;
+            if budget
+                .ticks
+                .is_some_and(|t| (self.clock)().wrapping_sub(start) >= t)
+            {
+                break;
+            }
+        }
+        claimed
+    }
+
+    /// Finishes deferred handling of a [`IrqFlags::ONESHOT`] interrupt `source` claimed in
+    /// `context`, re-enabling and completing it.
+    ///
+    /// The source is re-enabled first because the PLIC silently ignores completions for sources
+    /// that are not enabled in the target context (§9).
+    pub fn finish_oneshot(&self, plic: &mut Plic, ctx: usize, source: NonZeroU32) {
+        plic.enable(source, ctx);
+        self.complete(plic, ctx, source);
+    }
+
+    fn complete(&self, plic: &mut Plic, ctx: usize, source: NonZeroU32) {
+        plic.complete(ctx, source);
+        if let Some(observer) = self.observer {
+            observer.completed(source, ctx, (self.clock)());
+        }
+    }
+}
+
+/// Cleans up after a panicking handler while it unwinds; forgotten once the handlers return.
+struct PanicGuard<'a, const N: usize> {
+    dispatcher: &'a Dispatcher<N>,
+    plic: &'a mut Plic,
+    ctx: usize,
+    source: NonZeroU32,
+}
+
+impl<const N: usize> Drop for PanicGuard<'_, N> {
+    #[cold]
+    fn drop(&mut self) {
+        let (dispatcher, source) = (self.dispatcher, self.source);
+        for action in dispatcher.actions_of(source) {
+            action.disabled.store(true, Ordering::Release);
+        }
+        dispatcher.mask(self.plic, source);
+        // A oneshot source is disabled in the context, and completions of disabled sources
+        // are ignored (§9). It stays masked by its priority.
+        self.plic.enable(source, self.ctx);
+        self.plic.complete(self.ctx, source);
+        if let Some(observer) = dispatcher.observer {
+            observer.panicked(source, self.ctx, (dispatcher.clock)());
+        }
+    }
+}
+
+/// How much work [`Dispatcher::drain`] may do in one trap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Budget {
+    /// Maximum number of claims.
+    pub claims: u32,
+    /// Maximum clock ticks spent, if limited.
+    pub ticks: Option<u64>,
+}
+
+impl Budget {
+    /// One claim per trap, i.e. no coalescing.
+    pub const SINGLE: Self = Self::claims(1);
+
+    /// A budget of up to `claims` claims.
+    #[inline]
+    pub const fn claims(claims: u32) -> Self {
+        Self {
+            claims,
+            ticks: None,
+        }
+    }
+
+    /// Returns `self` additionally limited to `ticks` clock ticks.
+    #[inline]
+    pub const fn with_ticks(self, ticks: u64) -> Self {
+        Self {
+            ticks: Some(ticks),
+            ..self
+        }
+    }
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::SINGLE
+    }
+}
+
+/// Per-context coalescing budgets for contexts `0..C`, see [`Dispatcher::drain`].
+///
+/// Every context starts with [`Budget::SINGLE`].
+pub struct Coalescing<const C: usize> {
+    budgets: [Budget; C],
+}
+
+impl<const C: usize> Coalescing<C> {
+    /// Creates a table without coalescing.
+    pub const fn new() -> Self {
+        Self {
+            budgets: [Budget::SINGLE; C],
+        }
+    }
+
+    /// Sets the budget of `context`.
+    pub fn set_budget(&mut self, ctx: usize, budget: Budget) {
+        self.budgets[ctx] = budget;
+    }
+
+    /// Returns the budget of `context`; contexts beyond the table get [`Budget::SINGLE`].
+    #[inline]
+    pub fn budget(&self, ctx: usize) -> Budget {
+        self.budgets.get(ctx).copied().unwrap_or(Budget::SINGLE)
+    }
+
+    /// Handles interrupts in `context` within its budget, see [`Dispatcher::drain`].
+    #[inline]
+    pub fn handle<const N: usize>(
+        &self,
+        dispatcher: &Dispatcher<N>,
+        plic: &mut Plic,
+        ctx: usize,
+    ) -> u32 {
+        dispatcher.drain(plic, ctx, self.budget(ctx))
+    }
+}
+
+impl<const C: usize> Default for Coalescing<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An interrupt requested with [`Dispatcher::request_irq`].
+///
+/// Freeing needs the dispatcher and the PLIC, so it is not done on drop; call
+/// [`IrqHandle::free`] before the driver goes away.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[must_use = "the interrupt stays requested until the handle is freed"]
+pub struct IrqHandle {
+    source: NonZeroU32,
+    contexts: ContextMask,
+    slot: usize,
+    generation: u32,
+}
+
+impl IrqHandle {
+    /// Returns the requested source.
+    #[inline]
+    pub const fn source(&self) -> NonZeroU32 {
+        self.source
+    }
+
+    /// Returns the contexts the source was enabled in.
+    #[inline]
+    pub const fn contexts(&self) -> ContextMask {
+        self.contexts
+    }
+
+    /// Releases the interrupt, removing its handler.
+    ///
+    /// Once the last handler of the source is gone, the source is also disabled in the
+    /// requested contexts and gets back the priority it had before it was first requested.
+    /// Its wakeup mark is cleared if a request set it.
+    ///
+    /// Returns `false` without touching anything if the handler is no longer registered in
+    /// `dispatcher`, e.g. because it was removed with [`Dispatcher::unregister`] and the slot
+    /// reused, or `dispatcher` is not the one the interrupt was requested from.
+    #[cold]
+    pub fn free<const N: usize>(self, dispatcher: &mut Dispatcher<N>, plic: &mut Plic) -> bool {
+        let Some(slot) = dispatcher.actions.get_mut(self.slot).filter(|slot| {
+            slot.as_ref()
+                .is_some_and(|a| a.source == self.source && a.generation == self.generation)
+        }) else {
+            return false;
+        };
+        let Some(action) = slot.take() else {
+            return false;
+        };
+        let mut remaining = dispatcher
+            .actions
+            .iter_mut()
+            .flatten()
+            .filter(|a| a.source == self.source)
+            .peekable();
+        if remaining.peek().is_some() {
+            remaining.for_each(|a| a.clear_wake |= action.clear_wake);
+            return true;
+        }
+        for ctx in self.contexts.iter() {
+            plic.disable(self.source, ctx);
+        }
+        plic.set_priority(self.source, action.saved_priority);
+        if action.clear_wake {
+            plic.set_wake(self.source, false);
+        }
+        true
+    }
+}
+
+impl<const N: usize> Default for Dispatcher<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    use super::*;
+    use crate::testing::{host_plic, irq, registers, set_claim, set_pending};
+
+    #[test]
+    fn handle_one_claims_dispatches_and_completes() {
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+        fn handler(source: NonZeroU32) -> IrqReturn {
+            assert_eq!(source.get(), 5);
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            IrqReturn::Handled
+        }
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let mut dispatcher = Dispatcher::<4>::new();
+        dispatcher.register(irq(5), handler).unwrap();
+
+        assert!(!dispatcher.handle_one(&mut plic, 1));
+        set_claim(&plic, 1, Some(irq(5)));
+        assert!(dispatcher.handle_one(&mut plic, 1));
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        assert!(!plic.is_in_service(irq(5)));
+    }
+
+    #[test]
+    fn handle_one_completes_sources_without_handler() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let dispatcher = Dispatcher::<4>::new();
+
+        set_claim(&plic, 1, Some(irq(7)));
+        assert!(dispatcher.handle_one(&mut plic, 1));
+        assert!(!plic.is_in_service(irq(7)));
+    }
+
+    #[test]
+    fn oneshot_source_stays_disabled_until_deferred_handling_finishes() {
+        fn handler(_source: NonZeroU32) -> IrqReturn {
+            IrqReturn::Deferred
+        }
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let mut dispatcher = Dispatcher::<4>::new();
+        dispatcher
+            .register_with_flags(irq(5), handler, IrqFlags::ONESHOT)
+            .unwrap();
+        plic.enable(irq(5), 1);
+
+        set_claim(&plic, 1, Some(irq(5)));
+        assert!(dispatcher.handle_one(&mut plic, 1));
+        assert!(!plic.is_enabled(irq(5), 1));
+        assert_eq!(plic.in_service_context(irq(5)), Some(1));
+
+        dispatcher.finish_oneshot(&mut plic, 1, irq(5));
+        assert!(plic.is_enabled(irq(5), 1));
+        assert!(!plic.is_in_service(irq(5)));
+    }
+
+    #[test]
+    fn oneshot_source_handled_in_place_is_reenabled_and_completed() {
+        fn handler(_source: NonZeroU32) -> IrqReturn {
+            IrqReturn::Deferred
+        }
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let mut dispatcher = Dispatcher::<4>::new();
+        dispatcher
+            .register_with_flags(irq(5), handler, IrqFlags::ONESHOT | IrqFlags::NO_THREAD)
+            .unwrap();
+        plic.enable(irq(5), 1);
+
+        set_claim(&plic, 1, Some(irq(5)));
+        assert!(dispatcher.handle_one(&mut plic, 1));
+        assert!(plic.is_enabled(irq(5), 1));
+        assert!(!plic.is_in_service(irq(5)));
+    }
+
+    #[test]
+    fn lazy_disable_masks_only_once_the_source_fires() {
+        fn handler(_source: NonZeroU32) -> IrqReturn {
+            panic!("handler of a lazily disabled source called");
+        }
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let mut dispatcher = Dispatcher::<4>::new();
+        dispatcher.register(irq(5), handler).unwrap();
+        plic.set_priority(irq(5), 3);
+
+        dispatcher.disable_lazy(&mut plic, irq(5));
+        assert_eq!(plic.get_priority(irq(5)), 3);
+
+        set_claim(&plic, 1, Some(irq(5)));
+        assert!(dispatcher.handle_one(&mut plic, 1));
+        assert_eq!(plic.get_priority(irq(5)), 0);
+        assert!(!plic.is_in_service(irq(5)));
+
+        assert!(dispatcher.enable_lazy(&mut plic, irq(5)));
+        assert_eq!(plic.get_priority(irq(5)), 3);
+    }
+
+    #[test]
+    fn lazy_disable_of_pending_source_masks_right_away() {
+        fn handler(_source: NonZeroU32) -> IrqReturn {
+            IrqReturn::Handled
+        }
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let mut dispatcher = Dispatcher::<4>::new();
+        dispatcher.register(irq(5), handler).unwrap();
+        plic.set_priority(irq(5), 3);
+        set_pending(&plic, irq(5));
+
+        dispatcher.disable_lazy(&mut plic, irq(5));
+        assert_eq!(plic.get_priority(irq(5)), 0);
+        assert!(dispatcher.enable_lazy(&mut plic, irq(5)));
+        assert_eq!(plic.get_priority(irq(5)), 3);
+        assert!(!dispatcher.enable_lazy(&mut plic, irq(5)));
+    }
+
+    #[test]
+    fn panicking_handler_leaves_source_masked_and_completed() {
+        struct Panics(AtomicU32);
+        impl IrqObserver for Panics {
+            fn panicked(&self, source: NonZeroU32, ctx: usize, _now: u64) {
+                assert_eq!((source.get(), ctx), (5, 1));
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        static PANICS: Panics = Panics(AtomicU32::new(0));
+        fn handler(_source: NonZeroU32) -> IrqReturn {
+            panic!("driver bug");
+        }
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let mut dispatcher = Dispatcher::<4>::new();
+        dispatcher.set_observer(Some(&PANICS));
+        dispatcher
+            .register_with_flags(irq(5), handler, IrqFlags::ONESHOT)
+            .unwrap();
+        plic.set_priority(irq(5), 3);
+        plic.enable(irq(5), 1);
+
+        set_claim(&plic, 1, Some(irq(5)));
+        let result = catch_unwind(AssertUnwindSafe(|| dispatcher.handle_one(&mut plic, 1)));
+        assert!(result.is_err());
+        assert_eq!(PANICS.0.load(Ordering::Relaxed), 1);
+        assert_eq!(plic.get_priority(irq(5)), 0);
+        assert!(plic.is_enabled(irq(5), 1));
+        assert!(!plic.is_in_service(irq(5)));
+
+        // Lazily disabled, so the handler is not called again.
+        assert!(dispatcher.handle_one(&mut plic, 1));
+        assert!(dispatcher.enable_lazy(&mut plic, irq(5)));
+        assert_eq!(plic.get_priority(irq(5)), 3);
+    }
+
+    #[test]
+    fn drain_stops_at_the_claim_and_time_budget() {
+        static TICKS: AtomicU32 = AtomicU32::new(0);
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let mut dispatcher = Dispatcher::<4>::new();
+        dispatcher.set_clock(|| TICKS.fetch_add(1, Ordering::Relaxed).into());
+
+        assert_eq!(dispatcher.drain(&mut plic, 1, Budget::claims(3)), 0);
+        set_claim(&plic, 1, Some(irq(5)));
+        assert_eq!(dispatcher.drain(&mut plic, 1, Budget::claims(3)), 3);
+        let budget = Budget::claims(8).with_ticks(2);
+        assert_eq!(dispatcher.drain(&mut plic, 1, budget), 2);
+    }
+
+    #[test]
+    fn coalescing_drains_each_context_within_its_budget() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let dispatcher = Dispatcher::<4>::new();
+        let mut coalescing = Coalescing::<3>::new();
+        coalescing.set_budget(1, Budget::claims(4));
+
+        for ctx in [1, 2, 3] {
+            set_claim(&plic, ctx, Some(irq(5)));
+        }
+        assert_eq!(coalescing.handle(&dispatcher, &mut plic, 1), 4);
+        assert_eq!(coalescing.handle(&dispatcher, &mut plic, 2), 1);
+        assert_eq!(coalescing.handle(&dispatcher, &mut plic, 3), 1);
+    }
+
+    #[test]
+    fn observers_see_claims_and_completions() {
+        struct Events {
+            claimed: AtomicU32,
+            completed: AtomicU32,
+        }
+        impl IrqObserver for Events {
+            fn claimed(&self, source: NonZeroU32, ctx: usize, now: u64) {
+                assert_eq!((source.get(), ctx, now), (5, 1, 7));
+                self.claimed.fetch_add(1, Ordering::Relaxed);
+            }
+
+            fn completed(&self, source: NonZeroU32, ctx: usize, now: u64) {
+                assert_eq!((source.get(), ctx, now), (5, 1, 7));
+                self.completed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        static HOOKED: AtomicU32 = AtomicU32::new(0);
+        static OBSERVER: (Events, ClaimHook) = (
+            Events {
+                claimed: AtomicU32::new(0),
+                completed: AtomicU32::new(0),
+            },
+            ClaimHook(|source, ctx| {
+                assert_eq!((source.get(), ctx), (5, 1));
+                HOOKED.fetch_add(1, Ordering::Relaxed);
+            }),
+        );
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let mut dispatcher = Dispatcher::<4>::new();
+        dispatcher.set_clock(|| 7);
+        dispatcher.set_observer(Some(&OBSERVER));
+
+        set_claim(&plic, 1, Some(irq(5)));
+        assert_eq!(dispatcher.drain(&mut plic, 1, Budget::claims(2)), 2);
+        assert_eq!(OBSERVER.0.claimed.load(Ordering::Relaxed), 2);
+        assert_eq!(OBSERVER.0.completed.load(Ordering::Relaxed), 2);
+        assert_eq!(HOOKED.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn free_restores_what_request_irq_changed() {
+        fn handler(_source: NonZeroU32) -> IrqReturn {
+            IrqReturn::Handled
+        }
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, false);
+        let mut dispatcher = Dispatcher::<4>::new();
+        let contexts = ContextMask::single(1);
+        assert_eq!(
+            dispatcher
+                .request_irq(&mut plic, irq(5), handler, IrqFlags::WAKE, "dev", contexts)
+                .err(),
+            Some(PlicError::NoState)
+        );
+        assert!(dispatcher.handler(irq(5)).is_none());
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        plic.set_priority(irq(5), 3);
+        plic.set_wake(irq(6), true);
+        let flags = IrqFlags::WAKE | IrqFlags::SHARED;
+        let handles = [5, 6, 6].map(|source| {
+            dispatcher
+                .request_irq(&mut plic, irq(source), handler, flags, "dev", contexts)
+                .unwrap()
+        });
+        assert_eq!(plic.get_priority(irq(6)), Priority::MIN_ACTIVE.get());
+        assert!(plic.is_wake(irq(5)) && plic.is_enabled(irq(6), 1));
+
+        for handle in handles {
+            assert!(handle.free(&mut dispatcher, &mut plic));
+        }
+        assert_eq!(plic.get_priority(irq(5)), 3);
+        assert!(!plic.is_wake(irq(5)) && !plic.is_enabled(irq(5), 1));
+        assert_eq!(plic.get_priority(irq(6)), 0);
+        assert!(plic.is_wake(irq(6)) && !plic.is_enabled(irq(6), 1));
+    }
+}
@@ -0,0 +1,330 @@
+//! Security-domain partitioning of interrupt sources.
+//!
+//! Each source is assigned to exactly one [`Domain`], and each context is bound to at most one.
+//! [`DomainPlic`] wraps a [`Plic`] and only lets a caller touch a source or a context when it
+//! presents a [`DomainToken`] for the owning domain, giving TEE-style systems a single
+//! enforcement point over interrupt routing.
+
+use core::num::NonZeroU32;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::context::ContextMask;
+use crate::{Plic, PlicError, SOURCE_NUM};
+
+/// A security domain owning a set of interrupt sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Domain {
+    /// The kernel (normal world). All sources start out in this domain.
+    Kernel,
+    /// The secure world.
+    Secure,
+    /// Guest number `n`.
+    Guest(u8),
+}
+
+/// Capability proving the holder acts on behalf of a [`Domain`].
+///
+/// Tokens are minted by the owner of a [`DomainPlic`] with [`DomainPlic::mint`] and cannot be
+/// cloned, so handing one out is an explicit delegation. A token is only accepted by the
+/// wrapper that minted it.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DomainToken {
+    domain: Domain,
+    /// [`DomainPlic::id`] of the minting wrapper.
+    plic: u32,
+}
+
+impl DomainToken {
+    /// Returns the domain this token acts for.
+    #[inline]
+    pub const fn domain(&self) -> Domain {
+        self.domain
+    }
+}
+
+/// Source of [`DomainPlic::id`].
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+/// A [`Plic`] whose sources are partitioned between security domains.
+///
+/// The owner of this wrapper is the partitioning authority: it assigns sources, binds contexts
+/// and mints tokens. Every source operation requires a token of the domain the source is
+/// assigned to, and every context operation a token of the domain the context is bound to.
+///
+/// Only contexts `0..128`, see [`ContextMask::CAPACITY`], can be bound; operations on higher
+/// contexts fail with [`PlicError::InvalidContext`].
+pub struct DomainPlic {
+    plic: Plic,
+    /// Tells the tokens of this wrapper apart from those of others, assigned by the first
+    /// [`DomainPlic::mint`]; 0 until then.
+    id: u32,
+    owners: [Domain; SOURCE_NUM],
+    /// Domain each of the contexts `0..ContextMask::CAPACITY` is bound to.
+    context_owners: [Option<Domain>; ContextMask::CAPACITY],
+}
+
+impl DomainPlic {
+    /// Wraps `plic`, assigning every source to [`Domain::Kernel`].
+    ///
+    /// No context is bound yet, so no domain can use any until [`DomainPlic::bind_contexts`].
+    pub const fn new(plic: Plic) -> Self {
+        Self {
+            plic,
+            id: 0,
+            owners: [Domain::Kernel; SOURCE_NUM],
+            context_owners: [None; ContextMask::CAPACITY],
+        }
+    }
+
+    /// Mints a capability token for `domain`.
+    pub fn mint(&mut self, domain: Domain) -> DomainToken {
+        if self.id == 0 {
+            self.id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        }
+        DomainToken {
+            domain,
+            plic: self.id,
+        }
+    }
+
+    /// Returns the domain `token` acts for, if this wrapper minted it.
+    #[inline]
+    fn domain_for(&self, token: &DomainToken) -> Option<Domain> {
+        (token.plic == self.id).then_some(token.domain)
+    }
+
+    /// Assigns interrupt `source` to `domain`.
+    ///
+    /// Fails with [`PlicError::InvalidSource`] if `source` is not a PLIC source number.
+    pub fn assign(&mut self, source: NonZeroU32, domain: Domain) -> Result<(), PlicError> {
+        let owner = self
+            .owners
+            .get_mut(source.get() as usize)
+            .ok_or(PlicError::InvalidSource {
+                source: source.get(),
+            })?;
+        *owner = domain;
+        Ok(())
+    }
+
+    /// Returns the domain interrupt `source` is assigned to, `None` if it is not a PLIC
+    /// source number.
+    #[inline]
+    pub fn domain_of(&self, source: NonZeroU32) -> Option<Domain> {
+        self.owners.get(source.get() as usize).copied()
+    }
+
+    /// Binds the contexts of `contexts` to `domain`, taking them away from any other domain.
+    ///
+    /// Fails with [`PlicError::InvalidContext`] before binding anything if the PLIC does not
+    /// have one of the contexts.
+    pub fn bind_contexts(
+        &mut self,
+        domain: Domain,
+        contexts: ContextMask,
+    ) -> Result<(), PlicError> {
+        for ctx in contexts.iter() {
+            self.plic.check_context(ctx)?;
+        }
+        for ctx in contexts.iter() {
+            self.context_owners[ctx] = Some(domain);
+        }
+        Ok(())
+    }
+
+    /// Returns the contexts bound to `domain`.
+    pub fn contexts_of(&self, domain: Domain) -> ContextMask {
+        (0..ContextMask::CAPACITY)
+            .filter(|&ctx| self.context_owners[ctx] == Some(domain))
+            .fold(ContextMask::empty(), ContextMask::with)
+    }
+
+    /// Consumes the wrapper, returning the underlying [`Plic`].
+    pub fn into_inner(self) -> Plic {
+        self.plic
+    }
+
+    fn check(&self, token: &DomainToken, source: NonZeroU32) -> Result<(), PlicError> {
+        match self.domain_of(source) {
+            Some(domain) if self.domain_for(token) == Some(domain) => Ok(()),
+            Some(_) => Err(PlicError::AccessDenied {
+                source: source.get(),
+            }),
+            None => Err(PlicError::InvalidSource {
+                source: source.get(),
+            }),
+        }
+    }
+
+    fn check_context(&self, token: &DomainToken, ctx: usize) -> Result<(), PlicError> {
+        self.plic.check_context(ctx)?;
+        let Some(owner) = self.context_owners.get(ctx) else {
+            return Err(PlicError::InvalidContext { context: ctx });
+        };
+        if owner.is_some() && *owner == self.domain_for(token) {
+            Ok(())
+        } else {
+            Err(PlicError::ContextDenied { context: ctx })
+        }
+    }
+
+    /// Sets priority for interrupt `source` to `value`, see [`Plic::set_priority`].
+    pub fn set_priority(
+        &mut self,
+        token: &DomainToken,
+        source: NonZeroU32,
+        value: u32,
+    ) -> Result<(), PlicError> {
+        self.check(token, source)?;
+        self.plic.set_priority(source, value);
+        Ok(())
+    }
+
+    /// Gets priority for interrupt `source`, see [`Plic::get_priority`].
+    pub fn get_priority(&self, token: &DomainToken, source: NonZeroU32) -> Result<u32, PlicError> {
+        self.check(token, source)?;
+        Ok(self.plic.get_priority(source))
+    }
+
+    /// Checks if interrupt `source` is pending, see [`Plic::is_pending`].
+    pub fn is_pending(&self, token: &DomainToken, source: NonZeroU32) -> Result<bool, PlicError> {
+        self.check(token, source)?;
+        Ok(self.plic.is_pending(source))
+    }
+
+    /// Enables interrupt `source` in `context`, see [`Plic::enable`].
+    pub fn enable(
+        &mut self,
+        token: &DomainToken,
+        source: NonZeroU32,
+        ctx: usize,
+    ) -> Result<(), PlicError> {
+        self.check(token, source)?;
+        self.check_context(token, ctx)?;
+        self.plic.enable(source, ctx);
+        Ok(())
+    }
+
+    /// Disables interrupt `source` in `context`, see [`Plic::disable`].
+    pub fn disable(
+        &mut self,
+        token: &DomainToken,
+        source: NonZeroU32,
+        ctx: usize,
+    ) -> Result<(), PlicError> {
+        self.check(token, source)?;
+        self.check_context(token, ctx)?;
+        self.plic.disable(source, ctx);
+        Ok(())
+    }
+
+    /// Checks if interrupt `source` is enabled in `context`, see [`Plic::is_enabled`].
+    pub fn is_enabled(
+        &self,
+        token: &DomainToken,
+        source: NonZeroU32,
+        ctx: usize,
+    ) -> Result<bool, PlicError> {
+        self.check(token, source)?;
+        self.check_context(token, ctx)?;
+        Ok(self.plic.is_enabled(source, ctx))
+    }
+
+    /// Marks that interrupt `source` is completed in `context`, see [`Plic::complete`].
+    pub fn complete(
+        &mut self,
+        token: &DomainToken,
+        ctx: usize,
+        source: NonZeroU32,
+    ) -> Result<(), PlicError> {
+        self.check(token, source)?;
+        self.check_context(token, ctx)?;
+        self.plic.complete(ctx, source);
+        Ok(())
+    }
+
+    /// Gets the threshold of `context`, see [`Plic::get_threshold`].
+    pub fn get_threshold(&self, token: &DomainToken, ctx: usize) -> Result<u32, PlicError> {
+        self.check_context(token, ctx)?;
+        Ok(self.plic.get_threshold(ctx))
+    }
+
+    /// Sets the threshold of `context` to `value`, see [`Plic::set_threshold`].
+    pub fn set_threshold(
+        &mut self,
+        token: &DomainToken,
+        ctx: usize,
+        value: u32,
+    ) -> Result<(), PlicError> {
+        self.check_context(token, ctx)?;
+        self.plic.set_threshold(ctx, value);
+        Ok(())
+    }
+
+    /// Claims an interrupt in `context`, see [`Plic::claim`].
+    ///
+    /// A claimed source of another domain, enabled in the context before it was bound, is
+    /// completed again and reported as [`PlicError::AccessDenied`].
+    pub fn claim(
+        &mut self,
+        token: &DomainToken,
+        ctx: usize,
+    ) -> Result<Option<NonZeroU32>, PlicError> {
+        self.check_context(token, ctx)?;
+        let Some(source) = self.plic.claim(ctx) else {
+            return Ok(None);
+        };
+        if let Err(err) = self.check(token, source) {
+            self.plic.complete(ctx, source);
+            return Err(err);
+        }
+        Ok(Some(source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{host_plic, irq, registers};
+
+    #[test]
+    fn tokens_only_work_on_the_wrapper_that_minted_them() {
+        let (mut memory, mut other_memory) = (registers(), registers());
+        let mut plic = DomainPlic::new(host_plic(&mut memory, false));
+        let mut other = DomainPlic::new(host_plic(&mut other_memory, false));
+        let token = plic.mint(Domain::Kernel);
+        let foreign = other.mint(Domain::Kernel);
+        plic.bind_contexts(Domain::Kernel, ContextMask::single(1))
+            .unwrap();
+
+        assert_eq!(plic.set_priority(&token, irq(5), 1), Ok(()));
+        assert_eq!(plic.enable(&token, irq(5), 1), Ok(()));
+        assert_eq!(
+            plic.set_priority(&foreign, irq(5), 2),
+            Err(PlicError::AccessDenied { source: 5 })
+        );
+        assert_eq!(
+            plic.set_threshold(&foreign, 1, 2),
+            Err(PlicError::ContextDenied { context: 1 })
+        );
+    }
+
+    #[test]
+    fn contexts_beyond_the_mask_capacity_are_invalid() {
+        let mut memory = registers();
+        let mut plic = DomainPlic::new(host_plic(&mut memory, false));
+        let token = plic.mint(Domain::Kernel);
+
+        let ctx = ContextMask::CAPACITY;
+        assert_eq!(
+            plic.set_threshold(&token, ctx, 1),
+            Err(PlicError::InvalidContext { context: ctx })
+        );
+        assert_eq!(
+            plic.set_threshold(&token, 1, 1),
+            Err(PlicError::ContextDenied { context: 1 })
+        );
+    }
+}
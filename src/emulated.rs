@@ -0,0 +1,474 @@
+//! A PLIC implemented in memory, for hypervisors exposing a virtual PLIC to guests.
+//!
+//! [`EmulatedPlic`] keeps the register state of §3 to §9 in plain arrays. A hypervisor forwards
+//! trapped guest accesses to [`EmulatedPlic::read_at`] and [`EmulatedPlic::write_at`], feeds
+//! device interrupts in with [`EmulatedPlic::set_pending`], and asks
+//! [`EmulatedPlic::should_interrupt`] which virtual contexts need their external interrupt
+//! pending bit set.
+//!
+//! The gateway of every source behaves like an edge gateway that remembers one request: a
+//! request arriving while the source is claimed becomes pending again once it is completed.
+
+use core::num::NonZeroU32;
+
+use crate::{
+    CONTEXT_BASE, CONTEXT_NUM, CONTEXT_STRIDE, ENABLE_BASE, ENABLE_STRIDE, PENDING_BASE, PlicError,
+    SOURCE_NUM, U32_BITS,
+};
+
+const WORDS: usize = SOURCE_NUM / U32_BITS;
+
+/// Offset of the threshold register within a context page, see §7.
+const THRESHOLD_OFFSET: usize = 0;
+/// Offset of the claim/complete register within a context page, see §8 and §9.
+const CLAIM_OFFSET: usize = 4;
+
+/// How [`EmulatedPlic`] treats guest accesses to reserved registers, i.e. the gaps of the
+/// register map, the pages of contexts beyond `C` and the unused words of a context page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReservedAccess {
+    /// Reads return 0 and writes are ignored (RAZ/WI).
+    #[default]
+    Ignore,
+    /// Accesses fail with [`PlicError::InvalidAccess`], so the hypervisor can inject an access
+    /// fault into the guest.
+    Fault,
+}
+
+/// An in-memory PLIC with contexts `0..C`.
+pub struct EmulatedPlic<const C: usize> {
+    ndev: u32,
+    reserved: ReservedAccess,
+    priority_mask: u32,
+    priority: [u32; SOURCE_NUM],
+    pending: [u32; WORDS],
+    /// Requests that arrived while their source was in service.
+    latched: [u32; WORDS],
+    in_service: [u32; WORDS],
+    enable: [[u32; WORDS]; C],
+    threshold: [u32; C],
+}
+
+impl<const C: usize> EmulatedPlic<C> {
+    /// Creates a PLIC implementing sources `1..=ndev` with `priority_bits` priority and
+    /// threshold bits, with everything disabled and reserved registers RAZ/WI.
+    ///
+    /// `ndev` is capped at 1023. `C` must not exceed the 15872 contexts of the register map.
+    pub const fn new(ndev: u32, priority_bits: u32) -> Self {
+        const { assert!(C <= CONTEXT_NUM, "PLIC: too many emulated contexts") };
+        let max = SOURCE_NUM as u32 - 1;
+        Self {
+            ndev: if ndev > max { max } else { ndev },
+            reserved: ReservedAccess::Ignore,
+            priority_mask: match 1u32.checked_shl(priority_bits) {
+                Some(bit) => bit - 1,
+                None => u32::MAX,
+            },
+            priority: [0; SOURCE_NUM],
+            pending: [0; WORDS],
+            latched: [0; WORDS],
+            in_service: [0; WORDS],
+            enable: [[0; WORDS]; C],
+            threshold: [0; C],
+        }
+    }
+
+    /// Returns the number of implemented sources.
+    #[inline]
+    pub const fn ndev(&self) -> u32 {
+        self.ndev
+    }
+
+    /// Sets how accesses to reserved registers are handled.
+    #[cold]
+    pub fn set_reserved_access(&mut self, policy: ReservedAccess) {
+        self.reserved = policy;
+    }
+
+    /// Handles a guest load of `width` bytes at `offset` into the PLIC region.
+    ///
+    /// Only naturally aligned 32-bit accesses are supported. Unimplemented registers read as
+    /// 0, and so do reserved ones unless [`ReservedAccess::Fault`] is set. Reading a claim
+    /// register claims, see [`EmulatedPlic::claim`].
+    pub fn read_at(&mut self, offset: usize, width: usize) -> Result<u32, PlicError> {
+        Ok(match self.decode(offset, width)? {
+            Register::Priority(source) => self.priority[source],
+            Register::Pending(word) => self.pending[word],
+            Register::Enable(ctx, word) => self.enable[ctx][word],
+            Register::Threshold(ctx) => self.threshold[ctx],
+            Register::Claim(ctx) => self.claim(ctx).map_or(0, NonZeroU32::get),
+            Register::Reserved => 0,
+        })
+    }
+
+    /// Handles a guest store of `value`, `width` bytes wide, at `offset` into the PLIC region.
+    ///
+    /// Only naturally aligned 32-bit accesses are supported. Writes to read-only and
+    /// unimplemented registers or bits are ignored, and so are writes to reserved ones unless
+    /// [`ReservedAccess::Fault`] is set. Writing a claim register completes, see
+    /// [`EmulatedPlic::complete`].
+    pub fn write_at(&mut self, offset: usize, width: usize, value: u32) -> Result<(), PlicError> {
+        match self.decode(offset, width)? {
+            Register::Priority(source) => {
+                if (1..=self.ndev as usize).contains(&source) {
+                    self.priority[source] = value & self.priority_mask;
+                }
+            }
+            Register::Enable(ctx, word) => self.enable[ctx][word] = value & self.implemented(word),
+            Register::Threshold(ctx) => self.threshold[ctx] = value & self.priority_mask,
+            Register::Claim(ctx) => {
+                if let Some(source) = NonZeroU32::new(value) {
+                    self.complete(ctx, source);
+                }
+            }
+            Register::Pending(_) | Register::Reserved => {}
+        }
+        Ok(())
+    }
+
+    /// Raises interrupt `source`, as its gateway would on a device request.
+    ///
+    /// Requests for unimplemented sources are dropped.
+    pub fn set_pending(&mut self, source: NonZeroU32) {
+        if let Some((word, bit)) = self.locate(source) {
+            if self.in_service[word] & bit != 0 {
+                self.latched[word] |= bit;
+            } else {
+                self.pending[word] |= bit;
+            }
+        }
+    }
+
+    /// Withdraws a request of interrupt `source` that has not been claimed yet, e.g. when a
+    /// level-triggered line is deasserted.
+    pub fn clear_pending(&mut self, source: NonZeroU32) {
+        if let Some((word, bit)) = self.locate(source) {
+            self.pending[word] &= !bit;
+            self.latched[word] &= !bit;
+        }
+    }
+
+    /// Returns the source a claim in `context` would return: the pending, enabled source with
+    /// the highest priority above the threshold, the lowest ID winning ties. See §4 and §8.
+    pub fn highest_pending(&self, ctx: usize) -> Option<NonZeroU32> {
+        let threshold = self.threshold[ctx];
+        let mut best: Option<(u32, usize)> = None;
+        for word in 0..WORDS {
+            let mut candidates = self.pending[word] & self.enable[ctx][word];
+            while candidates != 0 {
+                let source = word * U32_BITS + candidates.trailing_zeros() as usize;
+                candidates &= candidates - 1;
+                let priority = self.priority[source];
+                if priority > threshold && best.is_none_or(|(p, _)| priority > p) {
+                    best = Some((priority, source));
+                }
+            }
+        }
+        best.and_then(|(_, source)| NonZeroU32::new(source as u32))
+    }
+
+    /// Returns whether `context` should have its external interrupt pending bit set.
+    #[inline]
+    pub fn should_interrupt(&self, ctx: usize) -> bool {
+        self.highest_pending(ctx).is_some()
+    }
+
+    /// Iterates over the contexts that should have their external interrupt pending bit set.
+    pub fn interrupting(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..C).filter(|&ctx| self.should_interrupt(ctx))
+    }
+
+    /// Sets priority for interrupt `source` to `value`, keeping only the implemented bits.
+    pub fn set_priority(&mut self, source: NonZeroU32, value: u32) {
+        if let Some(priority) = self.priority.get_mut(source.get() as usize)
+            && source.get() <= self.ndev
+        {
+            *priority = value & self.priority_mask;
+        }
+    }
+
+    /// Gets priority for interrupt `source`.
+    pub fn get_priority(&self, source: NonZeroU32) -> u32 {
+        self.priority
+            .get(source.get() as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Checks if interrupt `source` is pending.
+    pub fn is_pending(&self, source: NonZeroU32) -> bool {
+        self.locate(source)
+            .is_some_and(|(word, bit)| self.pending[word] & bit != 0)
+    }
+
+    /// Enables interrupt `source` in `context`.
+    pub fn enable(&mut self, source: NonZeroU32, ctx: usize) {
+        if let Some((word, bit)) = self.locate(source) {
+            self.enable[ctx][word] |= bit;
+        }
+    }
+
+    /// Disables interrupt `source` in `context`.
+    pub fn disable(&mut self, source: NonZeroU32, ctx: usize) {
+        if let Some((word, bit)) = self.locate(source) {
+            self.enable[ctx][word] &= !bit;
+        }
+    }
+
+    /// Checks if interrupt `source` is enabled in `context`.
+    pub fn is_enabled(&self, source: NonZeroU32, ctx: usize) -> bool {
+        self.locate(source)
+            .is_some_and(|(word, bit)| self.enable[ctx][word] & bit != 0)
+    }
+
+    /// Gets the threshold of `context`.
+    pub fn get_threshold(&self, ctx: usize) -> u32 {
+        self.threshold[ctx]
+    }
+
+    /// Sets the threshold of `context`, keeping only the implemented bits.
+    pub fn set_threshold(&mut self, ctx: usize, value: u32) {
+        self.threshold[ctx] = value & self.priority_mask;
+    }
+
+    /// Claims an interrupt in `context`, see [`EmulatedPlic::highest_pending`].
+    ///
+    /// The claimed source stops being pending and is in service until it is completed.
+    pub fn claim(&mut self, ctx: usize) -> Option<NonZeroU32> {
+        let source = self.highest_pending(ctx)?;
+        let (word, bit) = self.locate(source)?;
+        self.pending[word] &= !bit;
+        self.in_service[word] |= bit;
+        Some(source)
+    }
+
+    /// Completes interrupt `source` in `context`.
+    ///
+    /// Like the hardware, this is ignored if `source` is not enabled in `context` (§9). A
+    /// request that arrived while the source was in service becomes pending.
+    pub fn complete(&mut self, ctx: usize, source: NonZeroU32) {
+        let Some((word, bit)) = self.locate(source) else {
+            return;
+        };
+        if self.enable[ctx][word] & bit == 0 || self.in_service[word] & bit == 0 {
+            return;
+        }
+        self.in_service[word] &= !bit;
+        if self.latched[word] & bit != 0 {
+            self.latched[word] &= !bit;
+            self.pending[word] |= bit;
+        }
+    }
+
+    /// Returns the word and bit of an implemented `source`.
+    #[inline]
+    /// Decodes a guest access, applying the reserved-register policy.
+    fn decode(&self, offset: usize, width: usize) -> Result<Register, PlicError> {
+        check_access(offset, width)?;
+        match Register::decode(offset, C) {
+            Register::Reserved if self.reserved == ReservedAccess::Fault => {
+                Err(PlicError::InvalidAccess { offset, width })
+            }
+            register => Ok(register),
+        }
+    }
+
+    fn locate(&self, source: NonZeroU32) -> Option<(usize, u32)> {
+        let source = source.get();
+        (source <= self.ndev).then(|| {
+            let source = source as usize;
+            (source / U32_BITS, 1 << (source % U32_BITS))
+        })
+    }
+
+    /// Returns the bits of enable or pending word `word` that belong to implemented sources.
+    fn implemented(&self, word: usize) -> u32 {
+        (0..U32_BITS)
+            .filter(|bit| (1..=self.ndev as usize).contains(&(word * U32_BITS + bit)))
+            .fold(0, |mask, bit| mask | 1 << bit)
+    }
+}
+
+fn check_access(offset: usize, width: usize) -> Result<(), PlicError> {
+    if width != size_of::<u32>() || !offset.is_multiple_of(size_of::<u32>()) {
+        return Err(PlicError::InvalidAccess { offset, width });
+    }
+    Ok(())
+}
+
+/// A register of the PLIC region, see §3.
+enum Register {
+    Priority(usize),
+    Pending(usize),
+    Enable(usize, usize),
+    Threshold(usize),
+    Claim(usize),
+    Reserved,
+}
+
+impl Register {
+    fn decode(offset: usize, contexts: usize) -> Self {
+        if offset < PENDING_BASE {
+            return Self::Priority(offset / 4);
+        }
+        if offset < PENDING_BASE + WORDS * 4 {
+            return Self::Pending((offset - PENDING_BASE) / 4);
+        }
+        if (ENABLE_BASE..ENABLE_BASE + contexts * ENABLE_STRIDE).contains(&offset) {
+            let offset = offset - ENABLE_BASE;
+            return Self::Enable(offset / ENABLE_STRIDE, offset % ENABLE_STRIDE / 4);
+        }
+        if (CONTEXT_BASE..CONTEXT_BASE + contexts * CONTEXT_STRIDE).contains(&offset) {
+            let offset = offset - CONTEXT_BASE;
+            let ctx = offset / CONTEXT_STRIDE;
+            return match offset % CONTEXT_STRIDE {
+                THRESHOLD_OFFSET => Self::Threshold(ctx),
+                CLAIM_OFFSET => Self::Claim(ctx),
+                _ => Self::Reserved,
+            };
+        }
+        Self::Reserved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn irq(source: u32) -> NonZeroU32 {
+        NonZeroU32::new(source).unwrap()
+    }
+
+    const fn claim_offset(ctx: usize) -> usize {
+        CONTEXT_BASE + ctx * CONTEXT_STRIDE + CLAIM_OFFSET
+    }
+
+    #[test]
+    fn priority_and_enable_writes_keep_implemented_bits() {
+        let mut plic = EmulatedPlic::<2>::new(40, 3);
+        plic.write_at(4 * 5, 4, 0xff).unwrap();
+        assert_eq!(plic.read_at(4 * 5, 4), Ok(7));
+        plic.write_at(0, 4, 7).unwrap();
+        assert_eq!(plic.read_at(0, 4), Ok(0));
+        plic.write_at(4 * 41, 4, 7).unwrap();
+        assert_eq!(plic.read_at(4 * 41, 4), Ok(0));
+        plic.write_at(ENABLE_BASE + ENABLE_STRIDE, 4, u32::MAX)
+            .unwrap();
+        assert_eq!(plic.read_at(ENABLE_BASE + ENABLE_STRIDE, 4), Ok(!1));
+        plic.write_at(ENABLE_BASE + ENABLE_STRIDE + 4, 4, u32::MAX)
+            .unwrap();
+        assert_eq!(plic.read_at(ENABLE_BASE + ENABLE_STRIDE + 4, 4), Ok(0x1ff));
+    }
+
+    #[test]
+    fn claim_picks_highest_priority_then_lowest_id() {
+        let mut plic = EmulatedPlic::<1>::new(40, 3);
+        for (source, priority) in [(3, 2), (5, 6), (9, 6)] {
+            plic.set_priority(irq(source), priority);
+            plic.enable(irq(source), 0);
+            plic.set_pending(irq(source));
+        }
+        assert_eq!(plic.highest_pending(0), Some(irq(5)));
+        assert_eq!(plic.claim(0), Some(irq(5)));
+        assert_eq!(plic.claim(0), Some(irq(9)));
+        plic.set_threshold(0, 2);
+        assert!(!plic.should_interrupt(0));
+        assert_eq!(plic.claim(0), None);
+        plic.set_threshold(0, 1);
+        assert_eq!(plic.read_at(claim_offset(0), 4), Ok(3));
+        assert_eq!(plic.read_at(claim_offset(0), 4), Ok(0));
+    }
+
+    #[test]
+    fn requests_while_in_service_pend_after_completion() {
+        let mut plic = EmulatedPlic::<2>::new(8, 3);
+        plic.set_priority(irq(4), 1);
+        plic.enable(irq(4), 1);
+        plic.set_pending(irq(4));
+        assert!(plic.interrupting().eq([1]));
+        assert_eq!(plic.claim(1), Some(irq(4)));
+        plic.set_pending(irq(4));
+        assert!(!plic.is_pending(irq(4)));
+        plic.write_at(claim_offset(1), 4, 4).unwrap();
+        assert!(plic.is_pending(irq(4)));
+    }
+
+    #[test]
+    fn completion_of_disabled_source_is_ignored() {
+        let mut plic = EmulatedPlic::<1>::new(8, 3);
+        plic.set_priority(irq(2), 1);
+        plic.enable(irq(2), 0);
+        plic.set_pending(irq(2));
+        assert_eq!(plic.claim(0), Some(irq(2)));
+        plic.set_pending(irq(2));
+        plic.disable(irq(2), 0);
+        plic.complete(0, irq(2));
+        assert!(!plic.is_pending(irq(2)));
+        plic.enable(irq(2), 0);
+        plic.complete(0, irq(2));
+        assert!(plic.is_pending(irq(2)));
+    }
+
+    #[test]
+    fn clear_pending_withdraws_requests() {
+        let mut plic = EmulatedPlic::<1>::new(8, 3);
+        plic.set_priority(irq(1), 1);
+        plic.enable(irq(1), 0);
+        plic.set_pending(irq(1));
+        plic.clear_pending(irq(1));
+        assert_eq!(plic.claim(0), None);
+        plic.set_pending(irq(9));
+        assert!(!plic.is_pending(irq(9)));
+    }
+
+    #[test]
+    fn decodes_context_pages_and_rejects_bad_accesses() {
+        let mut plic = EmulatedPlic::<2>::new(8, 2);
+        plic.write_at(CONTEXT_BASE + CONTEXT_STRIDE, 4, 0xff)
+            .unwrap();
+        assert_eq!(plic.get_threshold(1), 3);
+        assert_eq!(plic.read_at(CONTEXT_BASE + 2 * CONTEXT_STRIDE, 4), Ok(0));
+        assert_eq!(plic.read_at(CONTEXT_BASE + 8, 4), Ok(0));
+        assert_eq!(plic.read_at(ENABLE_BASE + 2 * ENABLE_STRIDE, 4), Ok(0));
+        assert_eq!(
+            plic.read_at(2, 4),
+            Err(PlicError::InvalidAccess {
+                offset: 2,
+                width: 4
+            })
+        );
+        assert!(plic.write_at(0, 1, 0).is_err());
+    }
+
+    /// A hole between the pending and enable bits, a context page word that is neither
+    /// threshold nor claim, and the page of a context beyond `C`.
+    const RESERVED: [usize; 3] = [
+        PENDING_BASE + 0x80,
+        CONTEXT_BASE + 8,
+        CONTEXT_BASE + 2 * CONTEXT_STRIDE,
+    ];
+
+    #[test]
+    fn reserved_registers_are_raz_wi_by_default() {
+        let mut plic = EmulatedPlic::<2>::new(8, 2);
+        for offset in RESERVED {
+            assert_eq!(plic.write_at(offset, 4, !0), Ok(()));
+            assert_eq!(plic.read_at(offset, 4), Ok(0));
+        }
+    }
+
+    #[test]
+    fn reserved_registers_fault_when_asked() {
+        let mut plic = EmulatedPlic::<2>::new(8, 2);
+        plic.set_reserved_access(ReservedAccess::Fault);
+        for offset in RESERVED {
+            let fault = Err(PlicError::InvalidAccess { offset, width: 4 });
+            assert_eq!(plic.write_at(offset, 4, !0), fault.map(drop));
+            assert_eq!(plic.read_at(offset, 4), fault);
+        }
+        // Implemented and unimplemented registers are unaffected.
+        assert_eq!(plic.read_at(CONTEXT_BASE + CONTEXT_STRIDE, 4), Ok(0));
+        assert_eq!(plic.read_at(4 * 9, 4), Ok(0));
+    }
+}
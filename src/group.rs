@@ -0,0 +1,166 @@
+//! Named groups of interrupt sources.
+//!
+//! Device subsystems usually manage their interrupts as a unit. An [`IrqGroup`] names a set of
+//! sources so they can be enabled, disabled and prioritized together.
+
+use core::num::NonZeroU32;
+
+use crate::Plic;
+use crate::context::ContextMask;
+
+/// A named set of interrupt sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IrqGroup<'a> {
+    name: &'a str,
+    sources: &'a [NonZeroU32],
+}
+
+impl<'a> IrqGroup<'a> {
+    /// Creates a group called `name` with members `sources`.
+    #[inline]
+    pub const fn new(name: &'a str, sources: &'a [NonZeroU32]) -> Self {
+        Self { name, sources }
+    }
+
+    /// Returns the name of the group.
+    #[inline]
+    pub const fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns the members of the group.
+    #[inline]
+    pub const fn sources(&self) -> &'a [NonZeroU32] {
+        self.sources
+    }
+
+    /// Returns whether `source` is a member of the group.
+    pub fn contains(&self, source: NonZeroU32) -> bool {
+        self.sources.contains(&source)
+    }
+}
+
+impl Plic {
+    /// Enable every member of `group` in each context of `contexts`.
+    ///
+    /// See §6.
+    pub fn enable_group(&mut self, group: &IrqGroup<'_>, contexts: ContextMask) {
+        for &source in group.sources {
+            for ctx in contexts.iter() {
+                self.enable(source, ctx);
+            }
+        }
+    }
+
+    /// Disable every member of `group` in each context of `contexts`.
+    ///
+    /// See §6.
+    pub fn disable_group(&mut self, group: &IrqGroup<'_>, contexts: ContextMask) {
+        for &source in group.sources {
+            for ctx in contexts.iter() {
+                self.disable(source, ctx);
+            }
+        }
+    }
+
+    /// Set the priority of every member of `group` to `value`.
+    ///
+    /// See §4.
+    pub fn set_group_priority(&mut self, group: &IrqGroup<'_>, value: u32) {
+        for &source in group.sources {
+            self.set_priority(source, value);
+        }
+    }
+}
+
+/// Priorities and enables of a group stashed by [`Plic::suspend_group`], for groups of up to
+/// `N` members.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GroupSnapshot<const N: usize> {
+    contexts: ContextMask,
+    len: usize,
+    priorities: [u32; N],
+    enables: [ContextMask; N],
+}
+
+impl<const N: usize> GroupSnapshot<N> {
+    /// Returns the contexts whose enables were stashed.
+    #[inline]
+    pub const fn contexts(&self) -> ContextMask {
+        self.contexts
+    }
+
+    /// Returns the number of stashed sources.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether no source was stashed.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Plic {
+    /// Stash the priorities of the members of `group` and their enables in `contexts`, then
+    /// disable them there and set their priorities to 0.
+    ///
+    /// Used before power-gating a peripheral cluster; [`Plic::resume_group`] undoes it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `group` has more than `N` members.
+    #[cold]
+    pub fn suspend_group<const N: usize>(
+        &mut self,
+        group: &IrqGroup<'_>,
+        contexts: ContextMask,
+    ) -> GroupSnapshot<N> {
+        assert!(
+            group.sources.len() <= N,
+            "PLIC: group {} too large",
+            group.name
+        );
+        let mut snapshot = GroupSnapshot {
+            contexts,
+            len: group.sources.len(),
+            priorities: [0; N],
+            enables: [ContextMask::empty(); N],
+        };
+        for (i, &source) in group.sources.iter().enumerate() {
+            snapshot.priorities[i] = self.get_priority(source);
+            for ctx in contexts.iter() {
+                if self.is_enabled(source, ctx) {
+                    snapshot.enables[i] = snapshot.enables[i].with(ctx);
+                }
+                self.disable(source, ctx);
+            }
+            self.set_priority(source, 0);
+        }
+        snapshot
+    }
+
+    /// Restore the priorities and enables of `group` stashed by [`Plic::suspend_group`].
+    ///
+    /// `group` must be the group the snapshot was taken from.
+    #[cold]
+    pub fn resume_group<const N: usize>(
+        &mut self,
+        group: &IrqGroup<'_>,
+        snapshot: &GroupSnapshot<N>,
+    ) {
+        debug_assert_eq!(group.sources.len(), snapshot.len);
+        for (i, &source) in group.sources.iter().take(snapshot.len).enumerate() {
+            self.set_priority(source, snapshot.priorities[i]);
+            for ctx in snapshot.contexts.iter() {
+                if snapshot.enables[i].contains(ctx) {
+                    self.enable(source, ctx);
+                }
+            }
+        }
+    }
+}
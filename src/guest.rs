@@ -0,0 +1,181 @@
+//! Access to a guest's PLIC through the hypervisor virtual-machine load/store instructions.
+//!
+//! An L1 hypervisor passing a PLIC region through to an L2 guest can use [`GuestPlic`] to read
+//! and write the registers at the guest's addresses. Every access is a `hlv.w` or `hsv.w`, so
+//! it goes through the guest's two-stage address translation (`vsatp` and `hgatp`) exactly as
+//! a guest access would.
+
+use core::arch::asm;
+use core::num::NonZeroU32;
+
+use crate::{
+    CONTEXT_BASE, CONTEXT_NUM, CONTEXT_STRIDE, ENABLE_BASE, ENABLE_STRIDE, PENDING_BASE,
+    SOURCE_NUM, U32_BITS, parse_group_and_field,
+};
+
+/// A PLIC as seen from a guest, accessed with hypervisor load/store instructions.
+pub struct GuestPlic {
+    base: usize,
+}
+
+impl GuestPlic {
+    /// Creates a handle for the PLIC at guest address `base`.
+    ///
+    /// # Safety
+    ///
+    /// The hart must be in HS-mode, or in U-mode with `hstatus.HU` set, with `vsatp`, `hgatp`
+    /// and `hstatus.SPVP` describing the guest whose PLIC registers live at `base`.
+    #[inline]
+    pub const unsafe fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    #[inline]
+    fn read(&self, offset: usize) -> u32 {
+        let value: u32;
+        // SAFETY: guaranteed by the contract of `GuestPlic::new`.
+        unsafe {
+            asm!(
+                ".option push",
+                ".option arch, +h",
+                "hlv.w {value}, ({addr})",
+                ".option pop",
+                value = out(reg) value,
+                addr = in(reg) self.base + offset,
+                options(nostack, readonly),
+            );
+        }
+        value
+    }
+
+    #[inline]
+    fn write(&mut self, offset: usize, value: u32) {
+        // SAFETY: guaranteed by the contract of `GuestPlic::new`.
+        unsafe {
+            asm!(
+                ".option push",
+                ".option arch, +h",
+                "hsv.w {value}, ({addr})",
+                ".option pop",
+                value = in(reg) value,
+                addr = in(reg) self.base + offset,
+                options(nostack),
+            );
+        }
+    }
+
+    /// Returns `source` as an index, panicking if it is out of range.
+    #[inline]
+    #[track_caller]
+    fn source_index(source: NonZeroU32) -> usize {
+        let source = source.get() as usize;
+        assert!(
+            source < SOURCE_NUM,
+            "PLIC: interrupt source {source} out of range"
+        );
+        source
+    }
+
+    /// Returns the offset of the page of `context`, panicking if it is out of range.
+    #[inline]
+    #[track_caller]
+    fn context_offset(ctx: usize) -> usize {
+        assert!(ctx < CONTEXT_NUM, "PLIC: context {ctx} out of range");
+        CONTEXT_BASE + ctx * CONTEXT_STRIDE
+    }
+
+    /// Sets priority for interrupt `source` to `value`.
+    ///
+    /// See §4.
+    #[inline]
+    pub fn set_priority(&mut self, source: NonZeroU32, value: u32) {
+        self.write(Self::source_index(source) * 4, value);
+    }
+
+    /// Gets priority for interrupt `source`.
+    ///
+    /// See §4.
+    #[inline]
+    pub fn get_priority(&self, source: NonZeroU32) -> u32 {
+        self.read(Self::source_index(source) * 4)
+    }
+
+    /// Check if interrupt `source` is pending.
+    ///
+    /// See §5.
+    #[inline]
+    pub fn is_pending(&self, source: NonZeroU32) -> bool {
+        let (group, field) = parse_group_and_field(Self::source_index(source));
+        field.read(self.read(PENDING_BASE + group * 4)) != 0
+    }
+
+    #[inline]
+    #[track_caller]
+    fn enable_offset(ctx: usize, group: usize) -> usize {
+        assert!(ctx < CONTEXT_NUM, "PLIC: context {ctx} out of range");
+        ENABLE_BASE + ctx * ENABLE_STRIDE + group * (U32_BITS / 8)
+    }
+
+    /// Enable interrupt `source` in `context`.
+    ///
+    /// See §6.
+    #[inline]
+    pub fn enable(&mut self, source: NonZeroU32, ctx: usize) {
+        let (group, field) = parse_group_and_field(Self::source_index(source));
+        let offset = Self::enable_offset(ctx, group);
+        let word = self.read(offset);
+        self.write(offset, word | field.mask << field.shift);
+    }
+
+    /// Disable interrupt `source` in `context`.
+    ///
+    /// See §6.
+    #[inline]
+    pub fn disable(&mut self, source: NonZeroU32, ctx: usize) {
+        let (group, field) = parse_group_and_field(Self::source_index(source));
+        let offset = Self::enable_offset(ctx, group);
+        let word = self.read(offset);
+        self.write(offset, word & !(field.mask << field.shift));
+    }
+
+    /// Check if interrupt `source` is enabled in `context`.
+    ///
+    /// See §6.
+    #[inline]
+    pub fn is_enabled(&self, source: NonZeroU32, ctx: usize) -> bool {
+        let (group, field) = parse_group_and_field(Self::source_index(source));
+        field.read(self.read(Self::enable_offset(ctx, group))) != 0
+    }
+
+    /// Get interrupt threshold in `context`.
+    ///
+    /// See §7.
+    #[inline]
+    pub fn get_threshold(&self, ctx: usize) -> u32 {
+        self.read(Self::context_offset(ctx))
+    }
+
+    /// Set interrupt threshold for `context` to `value`.
+    ///
+    /// See §7.
+    #[inline]
+    pub fn set_threshold(&mut self, ctx: usize, value: u32) {
+        self.write(Self::context_offset(ctx), value);
+    }
+
+    /// Claim an interrupt in `context`, returning its source.
+    ///
+    /// See §8.
+    #[inline]
+    pub fn claim(&mut self, ctx: usize) -> Option<NonZeroU32> {
+        NonZeroU32::new(self.read(Self::context_offset(ctx) + 4))
+    }
+
+    /// Mark that interrupt identified by `source` is completed in `context`.
+    ///
+    /// See §9.
+    #[inline]
+    pub fn complete(&mut self, ctx: usize, source: NonZeroU32) {
+        self.write(Self::context_offset(ctx) + 4, source.get());
+    }
+}
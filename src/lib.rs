@@ -3,8 +3,60 @@
 
 #![no_std]
 
+/// Emits a `tracing` event at trace level with the `tracing` feature, and nothing otherwise.
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+pub mod abi;
+pub mod affinity;
+pub mod balance;
+pub mod blob;
+pub mod collect;
+pub mod context;
+pub mod deadline;
+#[cfg(feature = "fdt")]
+pub mod devicetree;
+pub mod diag;
+pub mod dispatch;
+pub mod domains;
+#[cfg(feature = "emulated")]
+pub mod emulated;
+pub mod group;
+#[cfg(all(
+    feature = "h-extension",
+    any(target_arch = "riscv32", target_arch = "riscv64")
+))]
+pub mod guest;
+pub mod manager;
+pub mod ops;
+pub mod platform;
+pub mod pm;
+pub mod profile;
+pub mod qos;
+pub mod quarantine;
+pub mod ratelimit;
+pub mod split;
+pub mod spurious;
+pub mod stats;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "trap")]
+pub mod trap;
+pub mod tuning;
+pub mod variant;
+
+use core::fmt;
+use core::mem::MaybeUninit;
 use core::num::NonZeroU32;
+use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
+#[cfg(feature = "relocatable")]
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::{AtomicU16, AtomicU32, Ordering};
 
 use tock_registers::{
     fields::Field,
@@ -13,15 +65,32 @@ use tock_registers::{
     registers::{ReadOnly, ReadWrite},
 };
 
+use crate::context::{ContextHandle, ContextMap};
+
 /// See §1.
 const SOURCE_NUM: usize = 1024;
 /// See §1.
 const CONTEXT_NUM: usize = 15872;
 
+/// Offset of the per-context register block, see §7.
+const CONTEXT_BASE: usize = 0x200000;
+/// Size of one per-context register page, see §7.
+const CONTEXT_STRIDE: usize = 0x1000;
+/// Offset of the interrupt pending bits, see §5.
+const PENDING_BASE: usize = 0x1000;
+/// Offset of the interrupt enable bits, see §6.
+const ENABLE_BASE: usize = 0x2000;
+/// Size of the enable bits of one context, see §6.
+const ENABLE_STRIDE: usize = 0x80;
+
 const U32_BITS: usize = u32::BITS as usize;
 
+/// In-service marker for a source that is not between claim and complete.
+const NOT_IN_SERVICE: u16 = u16::MAX;
+
 register_structs! {
-    ContextLocal {
+    /// Registers of one context page, see §7 to §9.
+    pub ContextLocal {
         /// Priority Threshold
         /// - The base address of Priority Thresholds register block is located at 4K alignment starts from offset 0x200000.
         (0x0000 => priority_threshold: ReadWrite<u32>),
@@ -58,14 +127,198 @@ register_structs! {
     }
 }
 
+// The raw offsets used for address arithmetic must agree with the register structs.
+const _: () = {
+    assert!(core::mem::offset_of!(PLICRegs, interrupt_pending) == PENDING_BASE);
+    assert!(core::mem::offset_of!(PLICRegs, interrupt_enable) == ENABLE_BASE);
+    assert!(size_of::<[ReadWrite<u32>; SOURCE_NUM / U32_BITS]>() == ENABLE_STRIDE);
+    assert!(core::mem::offset_of!(PLICRegs, contexts) == CONTEXT_BASE);
+    assert!(size_of::<ContextLocal>() == CONTEXT_STRIDE);
+};
+
+/// Errors reported by the checked PLIC operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum PlicError {
+    /// The caller's domain does not own interrupt `source`.
+    AccessDenied {
+        /// The interrupt source the access was attempted on.
+        source: u32,
+    },
+    /// A handler is already registered for interrupt `source`.
+    SourceBusy {
+        /// The interrupt source.
+        source: u32,
+    },
+    /// A fixed-capacity table is full.
+    NoSpace,
+    /// `context` does not exist on this PLIC.
+    InvalidContext {
+        /// The rejected context.
+        context: usize,
+    },
+    /// Interrupt `source` is 0 or above the number of implemented sources.
+    InvalidSource {
+        /// The rejected source number.
+        source: u32,
+    },
+    /// `base` is not a plausible PLIC base address.
+    InvalidBase {
+        /// The rejected address.
+        base: usize,
+    },
+    /// Interrupt `source` is not claimed on `context`.
+    NotClaimed {
+        /// The interrupt source.
+        source: u32,
+        /// The context the completion was attempted on.
+        context: usize,
+    },
+    /// A configuration blob is truncated or malformed.
+    InvalidBlob,
+    /// A configuration blob has a format version this crate does not understand.
+    UnsupportedVersion {
+        /// The version found in the blob.
+        version: u8,
+    },
+    /// The caller's domain is not bound to `context`.
+    ContextDenied {
+        /// The context the access was attempted on.
+        context: usize,
+    },
+    /// An emulated register access at `offset` has an unsupported `width` or alignment, or
+    /// hits a reserved register set to fault with `EmulatedPlic::set_reserved_access`.
+    InvalidAccess {
+        /// The offset into the PLIC region.
+        offset: usize,
+        /// The access width in bytes.
+        width: usize,
+    },
+    /// A priority or threshold in a configuration blob does not fit the implemented bits.
+    UnsupportedPriority {
+        /// The rejected value.
+        value: u32,
+    },
+    /// The operation needs a [`PlicState`], see [`Plic::set_state`].
+    NoState,
+    /// Interrupt `source` is fenced off with [`Plic::quarantine`].
+    Quarantined {
+        /// The quarantined source.
+        source: u32,
+    },
+    /// Hardware stored a different priority than was written, see
+    /// [`Plic::set_priority_checked`].
+    PriorityTruncated {
+        /// The value that was written.
+        requested: u32,
+        /// The value read back.
+        stored: u32,
+    },
+}
+
+impl fmt::Display for PlicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AccessDenied { source } => {
+                write!(f, "interrupt source {source} is owned by another domain")
+            }
+            Self::SourceBusy { source } => {
+                write!(f, "interrupt source {source} already has a handler")
+            }
+            Self::NoSpace => f.write_str("no space left in table"),
+            Self::InvalidContext { context } => write!(f, "invalid context {context}"),
+            Self::InvalidSource { source } => write!(f, "invalid interrupt source {source}"),
+            Self::InvalidBase { base } => write!(f, "invalid PLIC base address {base:#x}"),
+            Self::NotClaimed { source, context } => {
+                write!(
+                    f,
+                    "interrupt source {source} is not claimed on context {context}"
+                )
+            }
+            Self::InvalidBlob => f.write_str("malformed configuration blob"),
+            Self::UnsupportedVersion { version } => {
+                write!(f, "unsupported configuration blob version {version}")
+            }
+            Self::ContextDenied { context } => {
+                write!(f, "context {context} is bound to another domain")
+            }
+            Self::InvalidAccess { offset, width } => {
+                write!(f, "unsupported {width}-byte access at offset {offset:#x}")
+            }
+            Self::UnsupportedPriority { value } => {
+                write!(f, "priority {value} exceeds the implemented priority bits")
+            }
+            Self::NoState => f.write_str("no PLIC state installed"),
+            Self::Quarantined { source } => write!(f, "interrupt source {source} is quarantined"),
+            Self::PriorityTruncated { requested, stored } => {
+                write!(f, "priority {requested} was stored as {stored}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for PlicError {}
+
 /// Platform-Level Interrupt Controller.
 pub struct Plic {
+    #[cfg(not(feature = "relocatable"))]
     base: NonNull<PLICRegs>,
+    #[cfg(feature = "relocatable")]
+    base: AtomicPtr<PLICRegs>,
+    /// See [`Plic::set_state`].
+    state: Option<&'static PlicState>,
+    /// Number of implemented sources.
+    ndev: u32,
+    /// See [`Plic::set_completion_fault_handler`].
+    completion_fault: Option<fn(CompletionFault)>,
+    /// See [`Plic::set_variant`].
+    variant: &'static dyn variant::PlicVariant,
+    /// Installed context layout, see [`Plic::set_context_map`].
+    context_map: Option<ContextMap<'static>>,
+    /// Number of valid contexts.
+    num_contexts: usize,
 }
 
 unsafe impl Send for Plic {}
 unsafe impl Sync for Plic {}
 
+/// Software state of a [`Plic`], installed with [`Plic::set_state`].
+///
+/// It holds the in-service table and the wakeup and quarantine bitmaps, about 2.3 KiB, plus
+/// with the `strict` feature the 1.9 KiB bitmap of initialized contexts, so the handle itself
+/// stays small and kernels that need none of it pay nothing.
+pub struct PlicState {
+    /// Context each source was claimed on, or [`NOT_IN_SERVICE`].
+    in_service: [AtomicU16; SOURCE_NUM],
+    /// Wakeup-source bitmap, see [`Plic::set_wake`].
+    wake: [AtomicU32; SOURCE_NUM / U32_BITS],
+    /// Quarantined-source bitmap, see [`Plic::quarantine`].
+    quarantined: [AtomicU32; SOURCE_NUM / U32_BITS],
+    /// Contexts initialized with [`Plic::init_by_context`].
+    #[cfg(feature = "strict")]
+    initialized: [AtomicU32; CONTEXT_NUM / U32_BITS],
+}
+
+impl PlicState {
+    /// Creates the state of a PLIC with nothing in service, marked or quarantined.
+    pub const fn new() -> Self {
+        Self {
+            in_service: [const { AtomicU16::new(NOT_IN_SERVICE) }; SOURCE_NUM],
+            wake: [const { AtomicU32::new(0) }; SOURCE_NUM / U32_BITS],
+            quarantined: [const { AtomicU32::new(0) }; SOURCE_NUM / U32_BITS],
+            #[cfg(feature = "strict")]
+            initialized: [const { AtomicU32::new(0) }; CONTEXT_NUM / U32_BITS],
+        }
+    }
+}
+
+impl Default for PlicState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Plic {
     /// Create a new instance of the PLIC from the base address.
     ///
@@ -74,18 +327,275 @@ impl Plic {
     /// `base` must be a unique valid pointer to PLIC memory-mapped registers.
     #[inline]
     pub const unsafe fn new(base: NonNull<PLICRegs>) -> Self {
-        Self { base }
+        Self {
+            #[cfg(not(feature = "relocatable"))]
+            base,
+            #[cfg(feature = "relocatable")]
+            base: AtomicPtr::new(base.as_ptr()),
+            state: None,
+            ndev: SOURCE_NUM as u32 - 1,
+            completion_fault: None,
+            variant: &variant::Generic,
+            context_map: None,
+            num_contexts: CONTEXT_NUM,
+        }
     }
 
-    /// Initialize the PLIC by context, setting the priority threshold to 0.
+    /// Create a new instance of the PLIC from the base address, after checking that it is
+    /// plausible.
+    ///
+    /// Returns [`PlicError::InvalidBase`] unless `base` is 4 KiB-aligned and the 64 MiB
+    /// register region starting there fits in the widest address space of the target, instead
+    /// of constructing a handle that faults on first use. On 64-bit targets that is a 56-bit
+    /// physical address, or a virtual address sign-extended from bit 56 as in Sv57; see
+    /// [`Plic::try_new_with_width`] to check against the widths of the platform.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be a unique valid pointer to PLIC memory-mapped registers.
+    #[inline]
+    pub unsafe fn try_new(base: NonNull<PLICRegs>) -> Result<Self, PlicError> {
+        #[cfg(target_pointer_width = "64")]
+        let (phys_bits, virt_bits) = (56, 57);
+        #[cfg(not(target_pointer_width = "64"))]
+        let (phys_bits, virt_bits) = (usize::BITS, usize::BITS);
+        unsafe { Self::try_new_with_width(base, phys_bits, virt_bits) }
+    }
+
+    /// Like [`Plic::try_new`], but checks `base` against a `phys_bits`-bit physical and a
+    /// `virt_bits`-bit virtual address space, e.g. 56 and 39 on an Sv39 kernel.
+    ///
+    /// The register region must either lie below `2^phys_bits`, i.e. be a zero-extended
+    /// physical address or a lower-half virtual address, or be sign-extended from bit
+    /// `virt_bits - 1`, i.e. lie in the upper half of the virtual address space.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be a unique valid pointer to PLIC memory-mapped registers.
+    #[inline]
+    pub unsafe fn try_new_with_width(
+        base: NonNull<PLICRegs>,
+        phys_bits: u32,
+        virt_bits: u32,
+    ) -> Result<Self, PlicError> {
+        let addr = base.addr().get();
+        let Some(last) = addr.checked_add(size_of::<PLICRegs>() - 1) else {
+            return Err(PlicError::InvalidBase { base: addr });
+        };
+        let zero_extended = last.checked_shr(phys_bits).is_none_or(|high| high == 0);
+        let sign_extended = virt_bits > 0
+            && (!addr)
+                .checked_shr(virt_bits - 1)
+                .is_none_or(|high| high == 0);
+        if !addr.is_multiple_of(CONTEXT_STRIDE) || !(zero_extended || sign_extended) {
+            return Err(PlicError::InvalidBase { base: addr });
+        }
+        Ok(unsafe { Self::new(base) })
+    }
+
+    /// Check whether a PLIC responds at `base`, e.g. to pick between candidate addresses on
+    /// multi-platform kernels.
+    ///
+    /// Writes all ones to the priority of source 1 and expects the WARL register to keep a
+    /// non-empty, contiguous run of low bits, which neither RAM nor unmapped space does. The
+    /// previous priority is restored, so a present PLIC is left as found.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be mapped, and reading and writing the priority of source 1 there must not
+    /// disturb any other device.
+    #[cold]
+    pub unsafe fn detect(base: NonNull<PLICRegs>) -> bool {
+        let priority = &unsafe { base.as_ref() }.interrupt_priority[1];
+        let old = priority.get();
+        priority.set(!0);
+        let probed = priority.get();
+        priority.set(old);
+        probed != 0 && probed != !0 && probed & probed.wrapping_add(1) == 0
+    }
+
+    /// Initialize the PLIC by context, setting the priority threshold to 0, followed by the
+    /// per-context set-up of the installed [`variant`](Plic::set_variant).
+    #[cold]
     pub fn init_by_context(&mut self, ctx: usize) {
-        self.regs().contexts[ctx].priority_threshold.set(0);
+        self.context_regs(ctx).priority_threshold.set(0);
+        #[cfg(feature = "strict")]
+        if let Some(state) = self.state {
+            let (group, field) = parse_group_and_field(ctx);
+            state.initialized[group].fetch_or(field.mask << field.shift, Ordering::AcqRel);
+        }
+        let variant = self.variant;
+        variant.init_context(self, ctx);
+    }
+
+    /// With the `strict` feature, panics if `source` is above the implemented sources.
+    #[inline]
+    fn strict_source(&self, source: NonZeroU32) {
+        #[cfg(feature = "strict")]
+        assert!(
+            source.get() <= self.ndev,
+            "PLIC: interrupt source {source} is not implemented (ndev = {})",
+            self.ndev
+        );
+        let _ = source;
+    }
+
+    /// Installs the software state kept for this PLIC, enabling in-service tracking (see
+    /// [`Plic::is_in_service`]), wakeup sources and quarantine.
+    ///
+    /// Install it before [`Plic::init_by_context`] and the first claim, e.g. `plic.set_state(&STATE)` with a
+    /// `static STATE: PlicState = PlicState::new();`. Without a state these features are
+    /// inert, as documented on each of them.
+    #[cold]
+    pub fn set_state(&mut self, state: &'static PlicState) {
+        self.state = Some(state);
+    }
+
+    /// Set the number of implemented interrupt sources, e.g. from the `riscv,ndev`
+    /// device-tree property.
+    ///
+    /// Defaults to the specification maximum of 1023.
+    #[cold]
+    pub fn set_ndev(&mut self, ndev: u32) {
+        assert!((ndev as usize) < SOURCE_NUM);
+        self.ndev = ndev;
+    }
+
+    /// Returns the number of implemented interrupt sources, see [`Plic::set_ndev`].
+    #[inline]
+    pub const fn ndev(&self) -> u32 {
+        self.ndev
     }
 
+    /// Install the context layout of the platform, e.g. a preset from [`platform`] or one
+    /// derived from the device tree.
+    ///
+    /// From then on, every operation on a context outside the map panics instead of accessing
+    /// unmapped or aliased MMIO, and [`Plic::check_context`] rejects such contexts.
+    #[cold]
+    pub fn set_context_map(&mut self, map: ContextMap<'static>) {
+        assert!(map.len() <= CONTEXT_NUM);
+        self.num_contexts = map.len();
+        self.context_map = Some(map);
+    }
+
+    /// Returns the installed context layout, see [`Plic::set_context_map`].
+    #[inline]
+    pub const fn context_map(&self) -> Option<&ContextMap<'static>> {
+        self.context_map.as_ref()
+    }
+
+    /// Check that `context` exists on this PLIC.
+    ///
+    /// Without an installed context map, this only checks against the specification maximum
+    /// of 15872 contexts.
+    #[inline]
+    pub const fn check_context(&self, ctx: usize) -> Result<(), PlicError> {
+        if ctx < self.num_contexts {
+            Ok(())
+        } else {
+            Err(PlicError::InvalidContext { context: ctx })
+        }
+    }
+
+    /// Validate interrupt source number `source` against the implemented sources.
+    #[inline]
+    pub const fn irq(&self, source: u32) -> Result<IrqNumber, PlicError> {
+        match NonZeroU32::new(source) {
+            Some(n) if source <= self.ndev => Ok(IrqNumber(n)),
+            _ => Err(PlicError::InvalidSource { source }),
+        }
+    }
+
+    /// Returns the `(offset, len)` of the 4 KiB page holding the priority threshold and
+    /// claim/complete registers of `context`, relative to the PLIC base.
+    ///
+    /// A hypervisor can map exactly this page into a guest to pass a context through, while
+    /// keeping the priorities and enables protected.
+    ///
+    /// See §7.
+    #[inline]
+    pub const fn context_page(ctx: usize) -> (usize, usize) {
+        assert!(ctx < CONTEXT_NUM);
+        (CONTEXT_BASE + ctx * CONTEXT_STRIDE, CONTEXT_STRIDE)
+    }
+
+    /// Returns how many bytes from the PLIC base must be mapped to use contexts
+    /// `0..contexts`.
+    ///
+    /// Priorities, pending and enable bits occupy the first 2 MiB; each context adds one 4 KiB
+    /// page on top, so a two-context part needs about 2 MiB instead of the full 64 MiB window.
+    /// The driver only touches registers of contexts that are passed to it.
+    ///
+    /// See §3.
+    #[inline]
+    pub const fn mapped_len(contexts: usize) -> usize {
+        assert!(contexts <= CONTEXT_NUM);
+        CONTEXT_BASE + contexts * CONTEXT_STRIDE
+    }
+
+    /// Move the PLIC to a new base address, returning the old one.
+    ///
+    /// This is meant for KASLR-style relocation or late remapping of the MMIO window. The
+    /// protocol is:
+    ///
+    /// 1. Quiesce every other hart that may touch the PLIC, so none is inside a driver call.
+    /// 2. Make sure no source is between claim and complete; debug builds check this.
+    /// 3. Map the new window, call this method, then unmap the old window only after every
+    ///    hart has observed the new base (e.g. after an IPI round trip).
+    ///
+    /// # Safety
+    ///
+    /// `base` must satisfy the requirements of [`Plic::new`], and no other access to the PLIC
+    /// may be in progress during the swap.
+    #[cfg(feature = "relocatable")]
+    #[cold]
+    pub unsafe fn rebase(&self, base: NonNull<PLICRegs>) -> NonNull<PLICRegs> {
+        debug_assert!(
+            self.in_service_sources().next().is_none(),
+            "PLIC: rebase with a claim in flight"
+        );
+        let old = self.base.swap(base.as_ptr(), Ordering::AcqRel);
+        // SAFETY: only non-null pointers are ever stored.
+        unsafe { NonNull::new_unchecked(old) }
+    }
+
+    #[cfg(not(feature = "relocatable"))]
     const fn regs(&self) -> &PLICRegs {
         unsafe { self.base.as_ref() }
     }
 
+    #[cfg(feature = "relocatable")]
+    fn regs(&self) -> &PLICRegs {
+        unsafe { &*self.base.load(Ordering::Acquire) }
+    }
+
+    #[inline]
+    fn context_regs(&self, ctx: usize) -> &ContextLocal {
+        self.assert_context(ctx);
+        &self.regs().contexts[ctx]
+    }
+
+    #[inline]
+    fn enable_regs(&self, ctx: usize) -> &[ReadWrite<u32>; SOURCE_NUM / U32_BITS] {
+        self.assert_context(ctx);
+        &self.regs().interrupt_enable[ctx]
+    }
+
+    #[inline]
+    fn assert_context(&self, ctx: usize) {
+        if ctx >= self.num_contexts {
+            context_out_of_range(ctx, self.num_contexts);
+        }
+        #[cfg(feature = "supervisor-only")]
+        assert!(
+            self.context_map
+                .and_then(|m| m.target(ctx))
+                .is_none_or(|t| t.mode != context::Mode::Machine),
+            "PLIC: context {ctx} is a machine-mode context"
+        );
+    }
+
     /// Sets priority for interrupt `source` to `value`.
     ///
     /// Write `0` to priority `value` effectively disables this interrupt `source`, for the priority
@@ -94,10 +604,66 @@ impl Plic {
     /// The lowest active priority is priority `1`. The maximum priority depends on PLIC implementation
     /// and can be detected with [`Plic::probe_priority_bits`].
     ///
+    /// A source fenced off with [`Plic::quarantine`] keeps priority 0; the write is skipped.
+    /// Use [`Plic::set_priority_checked`] to be told.
+    ///
     /// See §4.
     #[inline]
     pub fn set_priority(&mut self, source: NonZeroU32, value: u32) {
+        _ = self.write_priority(source, value);
+    }
+
+    /// Body of [`Plic::set_priority`], shared with [`split::PlicShared`]. Fails with
+    /// [`PlicError::Quarantined`] without writing for a quarantined source.
+    #[inline]
+    fn write_priority(&self, source: NonZeroU32, value: u32) -> Result<(), PlicError> {
+        self.strict_source(source);
+        if self.is_quarantined(source) {
+            return Err(PlicError::Quarantined {
+                source: source.get(),
+            });
+        }
+        trace_event!(source = source.get(), priority = value, "set_priority");
         self.regs().interrupt_priority[source.get() as usize].set(value);
+        Ok(())
+    }
+
+    /// Sets priority for interrupt `source` to `value` and reads it back.
+    ///
+    /// Priority registers are WARL, so an implementation with fewer priority bits silently
+    /// drops the unsupported ones. This fails with [`PlicError::PriorityTruncated`] if the
+    /// stored value differs from `value`, and with [`PlicError::Quarantined`] without writing
+    /// if `source` is quarantined.
+    ///
+    /// See §4.
+    pub fn set_priority_checked(
+        &mut self,
+        source: NonZeroU32,
+        value: u32,
+    ) -> Result<(), PlicError> {
+        self.write_priority(source, value)?;
+        match self.get_priority(source) {
+            stored if stored == value => Ok(()),
+            stored => Err(PlicError::PriorityTruncated {
+                requested: value,
+                stored,
+            }),
+        }
+    }
+
+    /// Sets the priority of every implemented source, `1..=ndev`, to `value`.
+    ///
+    /// Quarantined sources keep priority 0, as with [`Plic::set_priority`].
+    ///
+    /// Used at bring-up, e.g. with `0` to silence everything or `1` to open all sources at the
+    /// lowest active priority.
+    ///
+    /// See §4.
+    #[cold]
+    pub fn set_priority_all(&mut self, value: u32) {
+        for source in self.sources() {
+            _ = self.write_priority(source, value);
+        }
     }
 
     /// Gets priority for interrupt `source`.
@@ -111,7 +677,7 @@ impl Plic {
     /// Probe maximum level of priority for interrupt `source`.
     ///
     /// See §4.
-    #[inline]
+    #[cold]
     pub fn probe_priority_bits(&mut self, source: NonZeroU32) -> u32 {
         self.regs().interrupt_priority[source.get() as usize].set(!0);
         self.regs().interrupt_priority[source.get() as usize].get()
@@ -128,12 +694,54 @@ impl Plic {
 
     /// Enable interrupt `source` in `context`.
     ///
+    /// Does nothing for a source fenced off with [`Plic::quarantine`].
+    ///
     /// See §6.
     #[inline]
     pub fn enable(&mut self, source: NonZeroU32, ctx: usize) {
+        self.write_enable(source, ctx, true);
+    }
+
+    /// Body of [`Plic::enable`] and [`Plic::disable`], shared with [`split::PlicContext`].
+    ///
+    /// With the `critical-section` feature the read-modify-write of the enable word runs in a
+    /// critical section, so it cannot interleave with another `&self` write of the same word.
+    #[inline]
+    fn write_enable(&self, source: NonZeroU32, ctx: usize, enabled: bool) {
+        self.strict_source(source);
+        if enabled && self.is_quarantined(source) {
+            return;
+        }
         let (group, field) = parse_group_and_field(source.get() as usize);
 
-        self.regs().interrupt_enable[ctx][group].modify(field.val(1));
+        if enabled {
+            trace_event!(source = source.get(), context = ctx, "enable");
+        } else {
+            trace_event!(source = source.get(), context = ctx, "disable");
+        }
+        #[cfg(feature = "critical-section")]
+        critical_section::with(|_| self.enable_regs(ctx)[group].modify(field.val(enabled as u32)));
+        #[cfg(not(feature = "critical-section"))]
+        self.enable_regs(ctx)[group].modify(field.val(enabled as u32));
+    }
+
+    /// Enable interrupt `source` in `context` and read the bit back.
+    ///
+    /// Some implementations hardwire enable bits, e.g. of sources not wired to a context.
+    /// This fails if the bit did not stick, so drivers learn about it at registration time
+    /// instead of waiting for an interrupt that never comes. A quarantined source also fails.
+    ///
+    /// See §6.
+    pub fn enable_verified(&mut self, source: NonZeroU32, ctx: usize) -> Result<(), NotEnableable> {
+        self.enable(source, ctx);
+        if self.is_enabled(source, ctx) {
+            Ok(())
+        } else {
+            Err(NotEnableable {
+                source,
+                context: ctx,
+            })
+        }
     }
 
     /// Disable interrupt `source` in `context`.
@@ -141,9 +749,32 @@ impl Plic {
     /// See §6.
     #[inline]
     pub fn disable(&mut self, source: NonZeroU32, ctx: usize) {
-        let (group, field) = parse_group_and_field(source.get() as usize);
+        self.write_enable(source, ctx, false);
+    }
 
-        self.regs().interrupt_enable[ctx][group].modify(field.val(0));
+    /// Enable interrupt `source` in `context` until the returned guard is dropped.
+    ///
+    /// The guard dereferences to the PLIC, so it can be used while the source is enabled.
+    ///
+    /// See §6.
+    #[inline]
+    pub fn enable_scoped(&mut self, source: NonZeroU32, ctx: usize) -> EnableGuard<'_> {
+        self.enable(source, ctx);
+        EnableGuard {
+            plic: self,
+            source,
+            ctx,
+        }
+    }
+
+    /// Disable all interrupt sources in `context`.
+    ///
+    /// See §6.
+    #[cold]
+    pub fn disable_all(&mut self, ctx: usize) {
+        for reg in self.enable_regs(ctx) {
+            reg.set(0);
+        }
     }
 
     /// Check if interrupt `source` is enabled in `context`.
@@ -153,7 +784,44 @@ impl Plic {
     pub fn is_enabled(&self, source: NonZeroU32, ctx: usize) -> bool {
         let (group, field) = parse_group_and_field(source.get() as usize);
 
-        self.regs().interrupt_enable[ctx][group].read(field) != 0
+        self.enable_regs(ctx)[group].read(field) != 0
+    }
+
+    /// Save the enable bits of all sources in `context`.
+    ///
+    /// See §6.
+    #[cold]
+    pub fn save_enables(&self, ctx: usize) -> EnableSnapshot {
+        let enables = self.enable_regs(ctx);
+        EnableSnapshot {
+            words: core::array::from_fn(|i| enables[i].get()),
+        }
+    }
+
+    /// Restore the enable bits of all sources in `context` from `snapshot`.
+    ///
+    /// See §6.
+    #[cold]
+    pub fn restore_enables(&mut self, ctx: usize, snapshot: &EnableSnapshot) {
+        let enables = self.enable_regs(ctx);
+        for (group, (reg, &word)) in enables.iter().zip(&snapshot.words).enumerate() {
+            reg.set(word & !self.quarantined_word(group));
+        }
+    }
+
+    /// Copy the enable bits of all sources from `template` to `target`.
+    ///
+    /// Brings a new hart online with the boot hart's routing in one write per enable word,
+    /// instead of replaying every driver's registration.
+    ///
+    /// See §6.
+    #[cold]
+    pub fn clone_enables(&mut self, template: usize, target: usize) {
+        let from = self.enable_regs(template);
+        let to = self.enable_regs(target);
+        for (group, (to, from)) in to.iter().zip(from).enumerate() {
+            to.set(from.get() & !self.quarantined_word(group));
+        }
     }
 
     /// Get interrupt threshold in `context`.
@@ -161,7 +829,7 @@ impl Plic {
     /// See §7.
     #[inline]
     pub fn get_threshold(&self, ctx: usize) -> u32 {
-        self.regs().contexts[ctx].priority_threshold.get()
+        self.context_regs(ctx).priority_threshold.get()
     }
 
     /// Set interrupt threshold for `context` to `value`.
@@ -169,16 +837,36 @@ impl Plic {
     /// See §7.
     #[inline]
     pub fn set_threshold(&mut self, ctx: usize, value: u32) {
-        self.regs().contexts[ctx].priority_threshold.set(value);
+        self.write_threshold(ctx, value);
     }
 
-    /// Probe maximum supported threshold value the `context` supports.
+    /// Body of [`Plic::set_threshold`], shared with [`split::PlicContext`].
+    #[inline]
+    fn write_threshold(&self, ctx: usize, value: u32) {
+        trace_event!(context = ctx, threshold = value, "set_threshold");
+        self.context_regs(ctx).priority_threshold.set(value);
+    }
+
+    /// Set interrupt threshold for `context` to `value`, returning the previous threshold.
+    ///
+    /// This is a plain read followed by a write, not an atomic exchange; callers sharing the
+    /// context between execution contexts must hold their PLIC lock across the call.
     ///
     /// See §7.
     #[inline]
+    pub fn swap_threshold(&mut self, ctx: usize, value: u32) -> u32 {
+        let old = self.get_threshold(ctx);
+        self.set_threshold(ctx, value);
+        old
+    }
+
+    /// Probe maximum supported threshold value the `context` supports.
+    ///
+    /// See §7.
+    #[cold]
     pub fn probe_threshold_bits(&mut self, ctx: usize) -> u32 {
-        self.regs().contexts[ctx].priority_threshold.set(!0);
-        self.regs().contexts[ctx].priority_threshold.get()
+        self.context_regs(ctx).priority_threshold.set(!0);
+        self.context_regs(ctx).priority_threshold.get()
     }
 
     /// Claim an interrupt in `context`, returning its source.
@@ -188,20 +876,621 @@ impl Plic {
     /// interrupt source has stopped to send interrupt signals. In this case, hart would instead
     /// poll for active interrupt by periodically calling the `claim` function.
     ///
+    /// With the `strict` feature and a [`PlicState`], this panics if `context` was not
+    /// initialized with [`Plic::init_by_context`].
+    ///
     /// See §8.
     #[inline]
     pub fn claim(&mut self, ctx: usize) -> Option<NonZeroU32> {
-        NonZeroU32::new(self.regs().contexts[ctx].interrupt_claim_complete.get())
+        self.strict_initialized(ctx);
+        self.assert_context(ctx);
+        // SAFETY: `assert_context` keeps `ctx` below `num_contexts`, at most `CONTEXT_NUM`.
+        unsafe { self.claim_unchecked(ctx) }
+    }
+
+    /// Validate `context` once, for use with [`Plic::claim_fast`] and [`Plic::complete_fast`].
+    pub fn context_handle(&self, ctx: usize) -> Result<ContextHandle, PlicError> {
+        self.check_context(ctx)?;
+        #[cfg(feature = "supervisor-only")]
+        if self
+            .context_map
+            .and_then(|m| m.target(ctx))
+            .is_some_and(|t| t.mode == context::Mode::Machine)
+        {
+            return Err(PlicError::InvalidContext { context: ctx });
+        }
+        Ok(ContextHandle(ctx))
+    }
+
+    /// Claim an interrupt in the context of `handle`.
+    ///
+    /// Like [`Plic::claim`], but the context was validated when the handle was created, so
+    /// the trap path is a single volatile load and the in-service bookkeeping store, without
+    /// bounds checks.
+    ///
+    /// See §8.
+    #[inline(always)]
+    pub fn claim_fast(&mut self, handle: ContextHandle) -> Option<NonZeroU32> {
+        self.strict_initialized(handle.0);
+        // SAFETY: handles are only created for contexts below `CONTEXT_NUM`.
+        unsafe { self.claim_unchecked(handle.0) }
+    }
+
+    /// # Safety
+    ///
+    /// `ctx` must be below `CONTEXT_NUM`.
+    #[inline(always)]
+    unsafe fn claim_unchecked(&self, ctx: usize) -> Option<NonZeroU32> {
+        let regs = unsafe { self.regs().contexts.get_unchecked(ctx) };
+        let source = NonZeroU32::new(regs.interrupt_claim_complete.get())?;
+        if let Some(state) = self.state {
+            // Claimed IDs are below `SOURCE_NUM`; the mask only spares the bounds check.
+            state.in_service[source.get() as usize % SOURCE_NUM]
+                .store(ctx as u16, Ordering::Release);
+        }
+        trace_event!(source = source.get(), context = ctx, "claim");
+        Some(source)
+    }
+
+    /// With the `strict` feature and a [`PlicState`], panics if `context` was not initialized.
+    #[inline(always)]
+    fn strict_initialized(&self, ctx: usize) {
+        #[cfg(feature = "strict")]
+        if let Some(state) = self.state {
+            let (group, field) = parse_group_and_field(ctx);
+            assert!(
+                field.read(state.initialized[group].load(Ordering::Acquire)) != 0,
+                "PLIC: claim from uninitialized context {ctx}"
+            );
+        }
+        let _ = ctx;
+    }
+
+    /// Claim an interrupt in `context` and disable it there before returning it.
+    ///
+    /// Closes the window in which a level-triggered line fires again on this context while
+    /// its handler is still being scheduled elsewhere. Finish with
+    /// [`Plic::complete_and_reenable`].
+    ///
+    /// See §6 and §8.
+    #[inline]
+    pub fn claim_masked(&mut self, ctx: usize) -> Option<NonZeroU32> {
+        let source = self.claim(ctx)?;
+        self.disable(source, ctx);
+        Some(source)
+    }
+
+    /// Claim an interrupt in `context` only if its priority is at least `min`.
+    ///
+    /// The threshold is raised to `min - 1` for the duration of the claim and restored
+    /// afterwards, so polled designs can drain high-priority work selectively. The specification
+    /// does not require claims to honor the threshold; should the hardware return a source below
+    /// `min` anyway, it is completed right away and `None` is returned. A level-triggered source
+    /// is forwarded again after that completion, but an edge-triggered one may be lost.
+    ///
+    /// See §7 and §8.
+    pub fn claim_above(&mut self, ctx: usize, min: Priority) -> Option<NonZeroU32> {
+        let old = self.swap_threshold(ctx, min.get() - 1);
+        let claimed = self.claim(ctx);
+        self.set_threshold(ctx, old);
+        let source = claimed?;
+        if self.get_priority(source) < min.get() {
+            self.complete(ctx, source);
+            return None;
+        }
+        Some(source)
+    }
+
+    /// Claim, handle with `f` and complete interrupts in `context` as long as their priority
+    /// is at least `min`, returning how many were handled.
+    ///
+    /// Lets real-time kernels service only urgent work inside a critical section. As with
+    /// [`Plic::claim_above`], the threshold is raised to `min - 1` meanwhile, and a source
+    /// below `min` returned by hardware that ignores the threshold for claims is completed
+    /// without calling `f`, which ends the drain; a level-triggered source is forwarded again.
+    ///
+    /// See §7, §8 and §9.
+    pub fn drain_claims_above(
+        &mut self,
+        ctx: usize,
+        min: Priority,
+        mut f: impl FnMut(NonZeroU32),
+    ) -> usize {
+        let old = self.swap_threshold(ctx, min.get() - 1);
+        let mut handled = 0;
+        while let Some(source) = self.claim(ctx) {
+            if self.get_priority(source) < min.get() {
+                self.complete(ctx, source);
+                break;
+            }
+            f(source);
+            self.complete(ctx, source);
+            handled += 1;
+        }
+        self.set_threshold(ctx, old);
+        handled
+    }
+
+    /// Claim interrupts in `context` until nothing is left or `buf` is full.
+    ///
+    /// Returns the number of claimed sources written to the start of `buf`. Each of them must be
+    /// completed with [`Plic::complete`] once handled.
+    ///
+    /// See §8.
+    pub fn claim_batch(&mut self, ctx: usize, buf: &mut [MaybeUninit<NonZeroU32>]) -> usize {
+        for (n, slot) in buf.iter_mut().enumerate() {
+            match self.claim(ctx) {
+                Some(source) => _ = slot.write(source),
+                None => return n,
+            }
+        }
+        buf.len()
+    }
+
+    /// Claim every currently claimable interrupt in `context` into `out`, together with its
+    /// priority, until nothing is left or `out` is full.
+    ///
+    /// Returns the number of entries pushed. Each claimed source must be completed with
+    /// [`Plic::complete`] once handled.
+    ///
+    /// See §8.
+    #[cfg(feature = "heapless")]
+    pub fn claim_all_into<const N: usize>(
+        &mut self,
+        ctx: usize,
+        out: &mut heapless::Vec<(NonZeroU32, u32), N>,
+    ) -> usize {
+        let mut n = 0;
+        while !out.is_full() {
+            let Some(source) = self.claim(ctx) else {
+                break;
+            };
+            _ = out.push((source, self.get_priority(source)));
+            n += 1;
+        }
+        n
     }
 
     /// Mark that interrupt identified by `source` is completed in `context`.
     ///
+    /// With the `strict` feature, this panics if `source` is not currently claimed on
+    /// `context` through this instance.
+    ///
     /// See §9.
     #[inline]
     pub fn complete(&mut self, ctx: usize, source: NonZeroU32) {
-        self.regs().contexts[ctx]
+        self.write_complete(ctx, source);
+    }
+
+    /// Body of [`Plic::complete`], shared with [`split::PlicContext`].
+    #[inline]
+    fn write_complete(&self, ctx: usize, source: NonZeroU32) {
+        self.strict_claimed(ctx, source);
+        trace_event!(source = source.get(), context = ctx, "complete");
+        self.context_regs(ctx)
             .interrupt_claim_complete
             .set(source.get());
+        self.end_service(ctx, source);
+    }
+
+    /// Mark that interrupt `source`, claimed with [`Plic::claim_fast`], is completed in the
+    /// context of `handle`.
+    ///
+    /// Like [`Plic::complete`], but the trap path is the bare volatile store to the completion
+    /// register; `source` must be a source claimed there. The in-service bookkeeping is left
+    /// to [`Plic::complete`]: the source keeps showing in [`Plic::is_in_service`] until it is
+    /// claimed again, no [`CompletionFault`] is reported, and a source quarantined while in
+    /// service stays enabled, though masked by its priority, in the context of `handle`.
+    ///
+    /// See §9.
+    #[inline(always)]
+    pub fn complete_fast(&mut self, handle: ContextHandle, source: NonZeroU32) {
+        trace_event!(source = source.get(), context = handle.0, "complete");
+        // SAFETY: handles are only created for contexts below `CONTEXT_NUM`.
+        let regs = unsafe { self.regs().contexts.get_unchecked(handle.0) };
+        regs.interrupt_claim_complete.set(source.get());
+    }
+
+    /// Clears the in-service slot of `source` after a completion in `context`, reporting the
+    /// completion if `source` was not in service there. A quarantined source is disabled in
+    /// `context` now that its completion has been written.
+    #[inline(always)]
+    fn end_service(&self, ctx: usize, source: NonZeroU32) {
+        let Some(state) = self.state else {
+            return;
+        };
+        let claimed_on =
+            state.in_service[source.get() as usize].swap(NOT_IN_SERVICE, Ordering::AcqRel);
+        if self.is_quarantined(source) {
+            // Left enabled by `Plic::quarantine` until this completion.
+            self.write_enable(source, ctx, false);
+        }
+        if claimed_on != ctx as u16
+            && let Some(handler) = self.completion_fault
+        {
+            handler(CompletionFault {
+                source,
+                context: ctx,
+                claimed_on: (claimed_on != NOT_IN_SERVICE).then_some(claimed_on as usize),
+            });
+        }
+    }
+
+    /// Installs `handler` to be told about completions of sources not in service on the
+    /// completing context, i.e. double completions and completions on the wrong context.
+    ///
+    /// The completion is still written; use [`Plic::try_complete`] to refuse it instead. Like
+    /// [`Plic::is_in_service`], this only knows about claims made through this instance.
+    #[cold]
+    pub fn set_completion_fault_handler(&mut self, handler: Option<fn(CompletionFault)>) {
+        self.completion_fault = handler;
+    }
+
+    /// With the `strict` feature and a [`PlicState`], panics if `source` is not claimed on
+    /// `context`.
+    #[inline(always)]
+    fn strict_claimed(&self, ctx: usize, source: NonZeroU32) {
+        #[cfg(feature = "strict")]
+        assert!(
+            self.state.is_none() || self.in_service_context(source) == Some(ctx),
+            "PLIC: completion of interrupt source {source} not claimed on context {ctx}"
+        );
+        let _ = (ctx, source);
+    }
+
+    /// Enable interrupt `source` in `context` again and complete it, ending a
+    /// [`Plic::claim_masked`] flow.
+    ///
+    /// The enable comes first: the PLIC silently ignores completions of sources not enabled
+    /// for the context.
+    ///
+    /// See §6 and §9.
+    #[inline]
+    pub fn complete_and_reenable(&mut self, ctx: usize, source: NonZeroU32) {
+        self.enable(source, ctx);
+        self.complete(ctx, source);
+    }
+
+    /// Mark that interrupt `source` is completed in `context`, after checking that it is
+    /// currently claimed there.
+    ///
+    /// Returns [`PlicError::NotClaimed`] without writing the completion register for double
+    /// completions and completions on the wrong context. Like [`Plic::is_in_service`], this
+    /// only knows about claims made through this instance; without a [`PlicState`] every
+    /// completion is written.
+    ///
+    /// See §9.
+    #[inline]
+    pub fn try_complete(&mut self, ctx: usize, source: NonZeroU32) -> Result<(), PlicError> {
+        if self.state.is_some() && self.in_service_context(source) != Some(ctx) {
+            return Err(PlicError::NotClaimed {
+                source: source.get(),
+                context: ctx,
+            });
+        }
+        self.complete(ctx, source);
+        Ok(())
+    }
+
+    /// Claim an interrupt in `context`, run `f` on its source and complete it afterwards.
+    ///
+    /// Returns `None` without calling `f` if there was nothing to claim. The completion is
+    /// written whatever `f` returns, so a closure bailing out early with `?` on a `Result` or
+    /// `Option` still completes the source. With the `tracing` feature, `f` runs inside an
+    /// `irq` span.
+    ///
+    /// See §8 and §9.
+    #[inline]
+    pub fn with_claim<R>(&mut self, ctx: usize, f: impl FnOnce(NonZeroU32) -> R) -> Option<R> {
+        let source = self.claim(ctx)?;
+        let ret = {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::trace_span!("irq", source = source.get(), context = ctx).entered();
+            f(source)
+        };
+        self.complete(ctx, source);
+        Some(ret)
+    }
+
+    /// Claim an interrupt in `context`, returning a guard that completes it when dropped.
+    ///
+    /// Returns `None` if there was nothing to claim. The guard dereferences to the PLIC, so
+    /// it can be used while the source is in service; completing the source through it as
+    /// well is a double completion.
+    ///
+    /// See §8 and §9.
+    #[inline]
+    pub fn claim_guard(&mut self, ctx: usize) -> Option<ClaimGuard<'_>> {
+        let source = self.claim(ctx)?;
+        Some(ClaimGuard {
+            plic: self,
+            source,
+            ctx,
+        })
+    }
+
+    /// Claim interrupts in `context` until the claim register returns 0, running `f` on each
+    /// source and completing it afterwards, see [`Plic::with_claim`].
+    ///
+    /// Returns the number of interrupts handled.
+    ///
+    /// See §8 and §9.
+    #[inline]
+    pub fn handle_pending(&mut self, ctx: usize, mut f: impl FnMut(NonZeroU32)) -> usize {
+        let mut handled = 0;
+        while self.with_claim(ctx, &mut f).is_some() {
+            handled += 1;
+        }
+        handled
+    }
+
+    /// Iterates over the implemented sources enabled in `context`, in ascending order.
+    ///
+    /// See [`collect`] for gathering them without allocation.
+    ///
+    /// See §6.
+    pub fn enabled_sources(&self, ctx: usize) -> impl Iterator<Item = NonZeroU32> + '_ {
+        let enables = self.enable_regs(ctx);
+        self.sources().filter(move |&source| {
+            let (group, field) = parse_group_and_field(source.get() as usize);
+            enables[group].read(field) != 0
+        })
+    }
+
+    /// Iterates over the implemented sources that are pending, in ascending order.
+    ///
+    /// See §5.
+    pub fn pending_sources(&self) -> impl Iterator<Item = NonZeroU32> + '_ {
+        self.sources().filter(|&source| self.is_pending(source))
+    }
+
+    /// Iterates over the sources claimed and not yet completed, as `(source, context)` pairs.
+    ///
+    /// See [`Plic::is_in_service`].
+    pub fn in_service_sources(&self) -> impl Iterator<Item = (NonZeroU32, usize)> + '_ {
+        self.sources()
+            .filter_map(|source| Some((source, self.in_service_context(source)?)))
+    }
+
+    /// Iterates over the implemented sources `1..=ndev`.
+    fn sources(&self) -> impl Iterator<Item = NonZeroU32> + use<> {
+        (1..=self.ndev).filter_map(NonZeroU32::new)
+    }
+
+    /// Check if interrupt `source` has been claimed and not yet completed.
+    ///
+    /// This is tracked in software by [`Plic::claim`] and [`Plic::complete`], so it only
+    /// reflects claims made through this instance, and only with a [`PlicState`] installed.
+    #[inline]
+    pub fn is_in_service(&self, source: NonZeroU32) -> bool {
+        self.in_service_context(source).is_some()
+    }
+
+    /// Returns the context interrupt `source` is currently being serviced on, if any.
+    ///
+    /// See [`Plic::is_in_service`].
+    #[inline]
+    pub fn in_service_context(&self, source: NonZeroU32) -> Option<usize> {
+        match self.state?.in_service[source.get() as usize].load(Ordering::Acquire) {
+            NOT_IN_SERVICE => None,
+            ctx => Some(ctx as usize),
+        }
+    }
+}
+
+/// Panic path of `Plic::assert_context`, kept out of line so the trap path stays small.
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn context_out_of_range(ctx: usize, contexts: usize) -> ! {
+    panic!("PLIC: context {ctx} out of range (contexts = {contexts})")
+}
+
+/// A completion of a source that was not in service on the completing context, see
+/// [`Plic::set_completion_fault_handler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CompletionFault {
+    /// The completed source.
+    pub source: NonZeroU32,
+    /// The context the completion was written to.
+    pub context: usize,
+    /// The context the source was in service on, `None` for a double completion.
+    pub claimed_on: Option<usize>,
+}
+
+/// Error returned by [`Plic::enable_verified`] when the enable bit did not stick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NotEnableable {
+    /// The interrupt source.
+    pub source: NonZeroU32,
+    /// The context it was enabled in.
+    pub context: usize,
+}
+
+impl fmt::Display for NotEnableable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "interrupt source {} cannot be enabled in context {}",
+            self.source, self.context
+        )
+    }
+}
+
+impl core::error::Error for NotEnableable {}
+
+/// Guard returned by [`Plic::enable_scoped`], disabling its source when dropped.
+#[must_use = "the source is disabled again as soon as the guard is dropped"]
+pub struct EnableGuard<'a> {
+    plic: &'a mut Plic,
+    source: NonZeroU32,
+    ctx: usize,
+}
+
+impl Deref for EnableGuard<'_> {
+    type Target = Plic;
+
+    #[inline]
+    fn deref(&self) -> &Plic {
+        self.plic
+    }
+}
+
+impl DerefMut for EnableGuard<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Plic {
+        self.plic
+    }
+}
+
+impl Drop for EnableGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.plic.disable(self.source, self.ctx);
+    }
+}
+
+/// Guard returned by [`Plic::claim_guard`], completing its source when dropped.
+#[must_use = "the source is completed as soon as the guard is dropped"]
+pub struct ClaimGuard<'a> {
+    plic: &'a mut Plic,
+    source: NonZeroU32,
+    ctx: usize,
+}
+
+impl ClaimGuard<'_> {
+    /// Returns the claimed source.
+    #[inline]
+    pub const fn source(&self) -> NonZeroU32 {
+        self.source
+    }
+
+    /// Returns the context the source was claimed in.
+    #[inline]
+    pub const fn context(&self) -> usize {
+        self.ctx
+    }
+}
+
+impl Deref for ClaimGuard<'_> {
+    type Target = Plic;
+
+    #[inline]
+    fn deref(&self) -> &Plic {
+        self.plic
+    }
+}
+
+impl DerefMut for ClaimGuard<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Plic {
+        self.plic
+    }
+}
+
+impl Drop for ClaimGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.plic.complete(self.ctx, self.source);
+    }
+}
+
+/// An interrupt source number validated against the implemented sources, see [`Plic::irq`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IrqNumber(NonZeroU32);
+
+impl IrqNumber {
+    /// Returns the source number.
+    #[inline]
+    pub const fn get(self) -> u32 {
+        self.0.get()
+    }
+}
+
+impl From<IrqNumber> for NonZeroU32 {
+    #[inline]
+    fn from(irq: IrqNumber) -> NonZeroU32 {
+        irq.0
+    }
+}
+
+impl From<IrqNumber> for u32 {
+    #[inline]
+    fn from(irq: IrqNumber) -> u32 {
+        irq.get()
+    }
+}
+
+/// An active interrupt priority, i.e. one that is not the reserved "never interrupt" value 0.
+///
+/// Priorities are ordered; a larger value wins arbitration, see §4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Priority(NonZeroU32);
+
+impl Priority {
+    /// The lowest active priority.
+    pub const MIN_ACTIVE: Self = Self(NonZeroU32::MIN);
+
+    /// Creates a priority from `value`, returning `None` for 0.
+    #[inline]
+    pub const fn new(value: u32) -> Option<Self> {
+        match NonZeroU32::new(value) {
+            Some(value) => Some(Self(value)),
+            None => None,
+        }
+    }
+
+    /// Creates a priority from `value`, returning `None` for 0 or values above `max`.
+    #[inline]
+    pub const fn new_bounded(value: u32, max: Priority) -> Option<Self> {
+        if value > max.get() {
+            return None;
+        }
+        Self::new(value)
+    }
+
+    /// Probes the maximum priority `plic` supports for interrupt `source`.
+    ///
+    /// The source priority is restored afterwards. Returns `None` if the source has no
+    /// writable priority bits, e.g. because it is not implemented.
+    pub fn max_for(plic: &mut Plic, source: NonZeroU32) -> Option<Self> {
+        let saved = plic.get_priority(source);
+        let max = plic.probe_priority_bits(source);
+        plic.set_priority(source, saved);
+        Self::new(max)
+    }
+
+    /// Returns the raw register value.
+    #[inline]
+    pub const fn get(self) -> u32 {
+        self.0.get()
+    }
+}
+
+impl From<Priority> for u32 {
+    #[inline]
+    fn from(priority: Priority) -> u32 {
+        priority.get()
+    }
+}
+
+/// The enable bits of all sources in one context, see [`Plic::save_enables`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EnableSnapshot {
+    words: [u32; SOURCE_NUM / U32_BITS],
+}
+
+impl EnableSnapshot {
+    /// Returns whether interrupt `source` is enabled in this snapshot.
+    #[inline]
+    pub fn is_enabled(&self, source: NonZeroU32) -> bool {
+        let (group, field) = parse_group_and_field(source.get() as usize);
+        field.read(self.words[group]) != 0
     }
 }
 
@@ -211,3 +1500,79 @@ fn parse_group_and_field(source: usize) -> (usize, Field<u32, ()>) {
     let field = Field::<u32, ()>::new(0b1, index);
     (group, field)
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::testing::{host_plic, irq, registers, set_claim};
+
+    #[test]
+    #[cfg_attr(feature = "strict", ignore = "strict panics on these completions")]
+    fn completion_faults_report_double_and_wrong_context_completions() {
+        static FAULTS: Mutex<Vec<CompletionFault>> = Mutex::new(Vec::new());
+        fn handler(fault: CompletionFault) {
+            FAULTS.lock().unwrap().push(fault);
+        }
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        plic.set_completion_fault_handler(Some(handler));
+        set_claim(&plic, 1, Some(irq(5)));
+
+        assert_eq!(plic.claim(1), Some(irq(5)));
+        plic.complete(1, irq(5));
+        assert!(FAULTS.lock().unwrap().is_empty());
+        plic.complete(1, irq(5));
+        assert_eq!(plic.claim(1), Some(irq(5)));
+        plic.complete(2, irq(5));
+        // The completion is still written.
+        assert_eq!(plic.context_regs(2).interrupt_claim_complete.get(), 5);
+
+        let faults = FAULTS.lock().unwrap();
+        assert_eq!(
+            faults[..],
+            [
+                CompletionFault {
+                    source: irq(5),
+                    context: 1,
+                    claimed_on: None,
+                },
+                CompletionFault {
+                    source: irq(5),
+                    context: 2,
+                    claimed_on: Some(1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn try_complete_refuses_completions_of_unclaimed_sources() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        set_claim(&plic, 1, Some(irq(5)));
+        let not_claimed = |context| PlicError::NotClaimed { source: 5, context };
+
+        assert_eq!(plic.try_complete(1, irq(5)), Err(not_claimed(1)));
+        assert_eq!(plic.claim(1), Some(irq(5)));
+        assert_eq!(plic.try_complete(2, irq(5)), Err(not_claimed(2)));
+        assert_eq!(plic.context_regs(2).interrupt_claim_complete.get(), 0);
+        assert_eq!(plic.try_complete(1, irq(5)), Ok(()));
+        assert!(!plic.is_in_service(irq(5)));
+        assert_eq!(plic.try_complete(1, irq(5)), Err(not_claimed(1)));
+    }
+
+    #[test]
+    fn try_complete_without_state_writes_every_completion() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, false);
+
+        assert_eq!(plic.try_complete(2, irq(5)), Ok(()));
+        assert_eq!(plic.context_regs(2).interrupt_claim_complete.get(), 5);
+    }
+}
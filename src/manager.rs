@@ -0,0 +1,114 @@
+//! A complete interrupt subsystem in one type.
+//!
+//! [`IrqManager`] ties together the PLIC handle, a [`Dispatcher`], an [`AffinityTable`] and an
+//! optional observer such as [`IrqStats`](crate::stats::IrqStats). Kernels that need more
+//! control can keep using the parts directly; the manager gives access to all of them.
+
+use core::num::NonZeroU32;
+
+use crate::affinity::{AffinityTable, HartMask};
+use crate::context::{ContextMask, Mode};
+use crate::dispatch::{Dispatcher, Handler, IrqFlags, IrqHandle, IrqObserver};
+use crate::platform::Platform;
+use crate::{Plic, PlicError};
+
+/// A PLIC with `N` handler slots and affinity for sources `0..S`, serving the contexts of
+/// one privilege mode.
+pub struct IrqManager<const N: usize, const S: usize> {
+    plic: Plic,
+    dispatcher: Dispatcher<N>,
+    affinity: AffinityTable<S>,
+    mode: Mode,
+}
+
+impl<const N: usize, const S: usize> IrqManager<N, S> {
+    /// Creates a manager for `plic` delivering to the `mode` contexts.
+    pub const fn new(plic: Plic, mode: Mode) -> Self {
+        Self {
+            plic,
+            dispatcher: Dispatcher::new(),
+            affinity: AffinityTable::new(),
+            mode,
+        }
+    }
+
+    /// Brings the PLIC into a known state for `platform`, see [`Platform::init`], and installs
+    /// `observer` on the dispatcher.
+    #[cold]
+    pub fn init(&mut self, platform: &Platform, observer: Option<&'static dyn IrqObserver>) {
+        platform.init(&mut self.plic, self.mode);
+        self.dispatcher.set_observer(observer);
+    }
+
+    /// Sets the harts interrupt `source` is routed to by later calls to
+    /// [`IrqManager::request`].
+    ///
+    /// Fails with [`PlicError::InvalidSource`] unless `source` is below `S`, see
+    /// [`AffinityTable::set_affinity`].
+    pub fn set_affinity(&mut self, source: NonZeroU32, mask: HartMask) -> Result<(), PlicError> {
+        self.affinity.set_affinity(source, mask)
+    }
+
+    /// Requests interrupt `source`, enabling it in the contexts its affinity allows.
+    ///
+    /// See [`Dispatcher::request_irq`]. Nothing is enabled before [`IrqManager::init`] has
+    /// installed a context map.
+    #[cold]
+    pub fn request(
+        &mut self,
+        source: NonZeroU32,
+        handler: Handler,
+        flags: IrqFlags,
+        name: &'static str,
+    ) -> Result<IrqHandle, PlicError> {
+        let contexts = self.plic.context_map().map_or(ContextMask::empty(), |map| {
+            self.affinity.contexts(source, map, self.mode)
+        });
+        self.dispatcher
+            .request_irq(&mut self.plic, source, handler, flags, name, contexts)
+    }
+
+    /// Releases an interrupt obtained from [`IrqManager::request`], see [`IrqHandle::free`].
+    #[cold]
+    pub fn free(&mut self, handle: IrqHandle) -> bool {
+        handle.free(&mut self.dispatcher, &mut self.plic)
+    }
+
+    /// Handles every interrupt pending in `context`, for the external interrupt trap.
+    ///
+    /// Returns the number of interrupts claimed.
+    #[inline]
+    pub fn handle_irq(&mut self, ctx: usize) -> usize {
+        let mut n = 0;
+        while self.dispatcher.handle_one(&mut self.plic, ctx) {
+            n += 1;
+        }
+        n
+    }
+
+    /// Handles every interrupt pending in the context of the hart with `contexts` for the mode
+    /// of the manager, see [`handle_external_trap`](crate::trap::handle_external_trap).
+    #[cfg(feature = "trap")]
+    #[inline]
+    pub fn handle_external_trap(&mut self, contexts: crate::context::HartContexts) -> usize {
+        crate::trap::handle_external_trap(&mut self.plic, &self.dispatcher, contexts, self.mode)
+    }
+
+    /// Returns the PLIC.
+    #[inline]
+    pub fn plic(&mut self) -> &mut Plic {
+        &mut self.plic
+    }
+
+    /// Returns the dispatcher.
+    #[inline]
+    pub fn dispatcher(&mut self) -> &mut Dispatcher<N> {
+        &mut self.dispatcher
+    }
+
+    /// Returns the affinity table.
+    #[inline]
+    pub fn affinity(&self) -> &AffinityTable<S> {
+        &self.affinity
+    }
+}
@@ -0,0 +1,141 @@
+//! Object-safe interface over the core PLIC operations.
+//!
+//! [`PlicOps`] lets kernels keep a `&'static mut dyn PlicOps` in their platform descriptors and
+//! pick the backend at runtime, e.g. a real [`Plic`] on the host, a `GuestPlic` when acting on a
+//! guest's view, or an `EmulatedPlic` backing a guest's virtual PLIC, without generics
+//! spreading through the interrupt layer.
+//!
+//! [`InterruptController`] is the smaller, chip-neutral subset that other interrupt drivers,
+//! e.g. an APLIC or a CLINT-based software interrupt chip, can implement as well, so a kernel's
+//! interrupt layer can bind to it instead of to this driver. Its methods carry an `_irq`
+//! suffix, so they never clash with the [`PlicOps`] or inherent methods of a PLIC.
+
+use core::num::NonZeroU32;
+
+use crate::Plic;
+
+/// The core PLIC operations, usable as a trait object.
+///
+/// Each method matches the inherent method of the same name on [`Plic`].
+pub trait PlicOps {
+    /// Sets priority for interrupt `source` to `value`, see [`Plic::set_priority`].
+    fn set_priority(&mut self, source: NonZeroU32, value: u32);
+    /// Gets priority for interrupt `source`, see [`Plic::get_priority`].
+    fn get_priority(&self, source: NonZeroU32) -> u32;
+    /// Checks if interrupt `source` is pending, see [`Plic::is_pending`].
+    fn is_pending(&self, source: NonZeroU32) -> bool;
+    /// Enables interrupt `source` in `context`, see [`Plic::enable`].
+    fn enable(&mut self, source: NonZeroU32, ctx: usize);
+    /// Disables interrupt `source` in `context`, see [`Plic::disable`].
+    fn disable(&mut self, source: NonZeroU32, ctx: usize);
+    /// Checks if interrupt `source` is enabled in `context`, see [`Plic::is_enabled`].
+    fn is_enabled(&self, source: NonZeroU32, ctx: usize) -> bool;
+    /// Gets the threshold of `context`, see [`Plic::get_threshold`].
+    fn get_threshold(&self, ctx: usize) -> u32;
+    /// Sets the threshold of `context`, see [`Plic::set_threshold`].
+    fn set_threshold(&mut self, ctx: usize, value: u32);
+    /// Claims an interrupt in `context`, see [`Plic::claim`].
+    fn claim(&mut self, ctx: usize) -> Option<NonZeroU32>;
+    /// Completes interrupt `source` in `context`, see [`Plic::complete`].
+    fn complete(&mut self, ctx: usize, source: NonZeroU32);
+}
+
+// `PlicOps` must stay object safe.
+const _: Option<&dyn PlicOps> = None;
+
+macro_rules! impl_plic_ops {
+    ($ty:ty $(, const $c:ident)?) => {
+        impl$(<const $c: usize>)? PlicOps for $ty {
+            #[inline]
+            fn set_priority(&mut self, source: NonZeroU32, value: u32) {
+                <$ty>::set_priority(self, source, value)
+            }
+            #[inline]
+            fn get_priority(&self, source: NonZeroU32) -> u32 {
+                <$ty>::get_priority(self, source)
+            }
+            #[inline]
+            fn is_pending(&self, source: NonZeroU32) -> bool {
+                <$ty>::is_pending(self, source)
+            }
+            #[inline]
+            fn enable(&mut self, source: NonZeroU32, ctx: usize) {
+                <$ty>::enable(self, source, ctx)
+            }
+            #[inline]
+            fn disable(&mut self, source: NonZeroU32, ctx: usize) {
+                <$ty>::disable(self, source, ctx)
+            }
+            #[inline]
+            fn is_enabled(&self, source: NonZeroU32, ctx: usize) -> bool {
+                <$ty>::is_enabled(self, source, ctx)
+            }
+            #[inline]
+            fn get_threshold(&self, ctx: usize) -> u32 {
+                <$ty>::get_threshold(self, ctx)
+            }
+            #[inline]
+            fn set_threshold(&mut self, ctx: usize, value: u32) {
+                <$ty>::set_threshold(self, ctx, value)
+            }
+            #[inline]
+            fn claim(&mut self, ctx: usize) -> Option<NonZeroU32> {
+                <$ty>::claim(self, ctx)
+            }
+            #[inline]
+            fn complete(&mut self, ctx: usize, source: NonZeroU32) {
+                <$ty>::complete(self, ctx, source)
+            }
+        }
+    };
+}
+
+impl_plic_ops!(Plic);
+#[cfg(all(
+    feature = "h-extension",
+    any(target_arch = "riscv32", target_arch = "riscv64")
+))]
+impl_plic_ops!(crate::guest::GuestPlic);
+#[cfg(feature = "emulated")]
+impl_plic_ops!(crate::emulated::EmulatedPlic<C>, const C);
+
+/// A minimal interrupt controller interface shared across interrupt drivers.
+///
+/// Interrupts are identified by a non-zero number, and each `target` is a delivery point
+/// with its own claim/complete interface: a PLIC context or an APLIC interrupt delivery
+/// control. Every [`PlicOps`] implements this interface.
+pub trait InterruptController {
+    /// Sets the priority of interrupt `irq`. Priority 0 never interrupts.
+    fn set_irq_priority(&mut self, irq: NonZeroU32, priority: u32);
+    /// Routes interrupt `irq` to `target`.
+    fn enable_irq(&mut self, irq: NonZeroU32, target: usize);
+    /// Stops routing interrupt `irq` to `target`.
+    fn disable_irq(&mut self, irq: NonZeroU32, target: usize);
+    /// Claims the highest-priority pending interrupt of `target`.
+    fn claim_irq(&mut self, target: usize) -> Option<NonZeroU32>;
+    /// Signals that `target` has finished handling interrupt `irq`.
+    fn complete_irq(&mut self, target: usize, irq: NonZeroU32);
+}
+
+impl<T: PlicOps + ?Sized> InterruptController for T {
+    #[inline]
+    fn set_irq_priority(&mut self, irq: NonZeroU32, priority: u32) {
+        PlicOps::set_priority(self, irq, priority)
+    }
+    #[inline]
+    fn enable_irq(&mut self, irq: NonZeroU32, target: usize) {
+        PlicOps::enable(self, irq, target)
+    }
+    #[inline]
+    fn disable_irq(&mut self, irq: NonZeroU32, target: usize) {
+        PlicOps::disable(self, irq, target)
+    }
+    #[inline]
+    fn claim_irq(&mut self, target: usize) -> Option<NonZeroU32> {
+        PlicOps::claim(self, target)
+    }
+    #[inline]
+    fn complete_irq(&mut self, target: usize, irq: NonZeroU32) {
+        PlicOps::complete(self, target, irq)
+    }
+}
@@ -0,0 +1,28 @@
+//! StarFive JH7110, as found on the VisionFive 2.
+//!
+//! Hart 0 is the S7 monitor core, which only has machine mode. Harts 1 to 4 are the U74
+//! application cores, each with a machine and a supervisor context.
+
+use super::{Platform, machine};
+use crate::context::{ContextMap, ContextTarget};
+use crate::variant::Generic;
+
+/// The JH7110 PLIC.
+pub const JH7110: Platform = Platform {
+    name: "StarFive JH7110",
+    base: 0x0c00_0000,
+    size: 0x0400_0000,
+    ndev: 136,
+    contexts: ContextMap::new(&[
+        machine(0),
+        machine(1),
+        Some(ContextTarget::supervisor(1)),
+        machine(2),
+        Some(ContextTarget::supervisor(2)),
+        machine(3),
+        Some(ContextTarget::supervisor(3)),
+        machine(4),
+        Some(ContextTarget::supervisor(4)),
+    ]),
+    variant: &Generic,
+};
@@ -0,0 +1,35 @@
+//! SpacemiT K1 (also sold as M1), as found on the Banana Pi BPI-F3.
+//!
+//! The eight X60 harts each have a machine and a supervisor context. The PLIC follows the
+//! SiFive layout and needs no quirks.
+
+use super::{Platform, machine};
+use crate::context::{ContextMap, ContextTarget};
+use crate::variant::Generic;
+
+/// The K1 PLIC.
+pub const K1: Platform = Platform {
+    name: "SpacemiT K1",
+    base: 0xe000_0000,
+    size: 0x0400_0000,
+    ndev: 159,
+    contexts: ContextMap::new(&[
+        machine(0),
+        Some(ContextTarget::supervisor(0)),
+        machine(1),
+        Some(ContextTarget::supervisor(1)),
+        machine(2),
+        Some(ContextTarget::supervisor(2)),
+        machine(3),
+        Some(ContextTarget::supervisor(3)),
+        machine(4),
+        Some(ContextTarget::supervisor(4)),
+        machine(5),
+        Some(ContextTarget::supervisor(5)),
+        machine(6),
+        Some(ContextTarget::supervisor(6)),
+        machine(7),
+        Some(ContextTarget::supervisor(7)),
+    ]),
+    variant: &Generic,
+};
@@ -0,0 +1,83 @@
+//! Presets for common RISC-V platforms.
+//!
+//! Each preset describes where the PLIC lives, how many sources it implements and how its
+//! contexts map to harts, as found in the platform's device tree.
+
+pub mod jh7110;
+pub mod k1;
+pub mod sg2042;
+
+use crate::Plic;
+use crate::context::{ContextMap, ContextTarget, Mode};
+use crate::variant::PlicVariant;
+
+/// The PLIC parameters of a platform.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Platform {
+    /// Human-readable platform name.
+    pub name: &'static str,
+    /// Physical base address of the PLIC.
+    pub base: u64,
+    /// Size of the PLIC MMIO region.
+    pub size: usize,
+    /// Number of implemented interrupt sources (`riscv,ndev`).
+    pub ndev: u32,
+    /// Context layout (`interrupts-extended`).
+    pub contexts: ContextMap<'static>,
+    /// Vendor variant of the PLIC, see [`Plic::set_variant`].
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub variant: &'static dyn PlicVariant,
+}
+
+impl Platform {
+    /// Brings `plic` into a known state for a kernel running in `mode`.
+    ///
+    /// Installs the variant and records the source count and context map, then for every
+    /// context of `mode` disables all sources and initializes it with
+    /// [`Plic::init_by_context`]. Contexts of other modes, e.g. the machine-mode contexts owned
+    /// by the SBI firmware, are left alone.
+    #[cold]
+    pub fn init(&self, plic: &mut Plic, mode: Mode) {
+        plic.set_variant(self.variant);
+        plic.set_ndev(self.ndev);
+        plic.set_context_map(self.contexts);
+        for (ctx, _) in self.contexts.contexts().filter(|(_, t)| t.mode == mode) {
+            plic.disable_all(ctx);
+            plic.init_by_context(ctx);
+        }
+    }
+}
+
+/// The preset entry for the machine context of `hart`.
+///
+/// With the `supervisor-only` feature, machine contexts are listed as unconnected, so nothing
+/// built from a preset ever touches them.
+#[cfg(not(feature = "supervisor-only"))]
+pub(crate) const fn machine(hart: usize) -> Option<ContextTarget> {
+    Some(ContextTarget::machine(hart))
+}
+
+/// The preset entry for the machine context of `hart`.
+///
+/// With the `supervisor-only` feature, machine contexts are listed as unconnected, so nothing
+/// built from a preset ever touches them.
+#[cfg(feature = "supervisor-only")]
+pub(crate) const fn machine(_hart: usize) -> Option<ContextTarget> {
+    None
+}
+
+/// Builds the common layout where every hart has a machine context followed by a supervisor
+/// context, in hart order. `N` is the number of contexts, twice the number of harts.
+pub const fn machine_supervisor_pairs<const N: usize>() -> [Option<ContextTarget>; N] {
+    let mut targets = [None; N];
+    let mut ctx = 0;
+    while ctx < N {
+        targets[ctx] = match ctx % 2 {
+            0 => machine(ctx / 2),
+            _ => Some(ContextTarget::supervisor(ctx / 2)),
+        };
+        ctx += 1;
+    }
+    targets
+}
@@ -0,0 +1,27 @@
+//! Sophgo SG2042, as found on the Milk-V Pioneer.
+//!
+//! All 64 C920 harts share a single PLIC, each with a machine and a supervisor context, giving
+//! 128 contexts. The PLIC is the [`THead`] variant; the firmware grants supervisor access to it,
+//! so the preset leaves `PLIC_CTRL` alone.
+
+use super::{Platform, machine_supervisor_pairs};
+use crate::Plic;
+use crate::context::{ContextMap, ContextTarget};
+use crate::variant::THead;
+
+const CONTEXTS: [Option<ContextTarget>; 128] = machine_supervisor_pairs();
+
+/// The SG2042 PLIC.
+pub const SG2042: Platform = Platform {
+    name: "Sophgo SG2042",
+    base: 0x70_9000_0000,
+    size: 0x0400_0000,
+    ndev: 224,
+    contexts: ContextMap::new(&CONTEXTS),
+    variant: &THead {
+        supervisor_access: None,
+    },
+};
+
+// Every context page must lie inside the MMIO window.
+const _: () = assert!(Plic::mapped_len(SG2042.contexts.len()) <= SG2042.size);
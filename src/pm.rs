@@ -0,0 +1,60 @@
+//! Power management: wakeup sources and the PLIC part of system suspend.
+
+use core::num::NonZeroU32;
+use core::sync::atomic::Ordering;
+
+use tock_registers::interfaces::Writeable;
+
+use crate::{EnableSnapshot, Plic, parse_group_and_field};
+
+impl Plic {
+    /// Marks interrupt `source` as a wakeup source, or clears the mark.
+    ///
+    /// Wakeup sources stay enabled across [`Plic::enter_suspend`]. The marks live in the
+    /// [`PlicState`](crate::PlicState), so marking a source panics without one.
+    pub fn set_wake(&self, source: NonZeroU32, wake: bool) {
+        let (group, field) = parse_group_and_field(source.get() as usize);
+        let mask = field.mask << field.shift;
+        match self.state {
+            Some(state) if wake => state.wake[group].fetch_or(mask, Ordering::AcqRel),
+            Some(state) => state.wake[group].fetch_and(!mask, Ordering::AcqRel),
+            None if wake => panic!("PLIC: wakeup sources need a PlicState"),
+            None => return,
+        };
+    }
+
+    /// Checks if interrupt `source` is marked as a wakeup source.
+    #[inline]
+    pub fn is_wake(&self, source: NonZeroU32) -> bool {
+        let (group, field) = parse_group_and_field(source.get() as usize);
+        field.read(self.wake_word(group)) != 0
+    }
+
+    /// Returns the wakeup-source bits of enable word `group`.
+    #[inline]
+    pub(crate) fn wake_word(&self, group: usize) -> u32 {
+        self.state
+            .map_or(0, |state| state.wake[group].load(Ordering::Acquire))
+    }
+
+    /// Prepares `context` for system sleep, disabling every source that is not marked with
+    /// [`Plic::set_wake`].
+    ///
+    /// Returns the enable state before suspend, to be handed to [`Plic::exit_suspend`]. Wakeup
+    /// sources keep the enable state they had; they are not enabled if they were disabled.
+    #[cold]
+    pub fn enter_suspend(&mut self, ctx: usize) -> EnableSnapshot {
+        let saved = self.save_enables(ctx);
+        let enables = self.enable_regs(ctx);
+        for (group, (reg, &word)) in enables.iter().zip(&saved.words).enumerate() {
+            reg.set(word & self.wake_word(group));
+        }
+        saved
+    }
+
+    /// Restores the enable state of `context` saved by [`Plic::enter_suspend`].
+    #[cold]
+    pub fn exit_suspend(&mut self, ctx: usize, saved: &EnableSnapshot) {
+        self.restore_enables(ctx, saved);
+    }
+}
@@ -0,0 +1,62 @@
+//! Named threshold presets.
+//!
+//! A [`ThresholdProfile`] gives a symbolic name to a threshold value, so schedulers can switch
+//! the class of interrupts a hart accepts without spreading raw priorities around.
+
+use crate::{Plic, Priority};
+
+/// A named threshold value, see [`Plic::apply_profile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ThresholdProfile {
+    name: &'static str,
+    threshold: u32,
+}
+
+impl ThresholdProfile {
+    /// Accepts every enabled interrupt with a non-zero priority.
+    pub const NORMAL: Self = Self::new("normal", 0);
+
+    /// Accepts no interrupt. The register keeps its highest implemented value.
+    pub const MASKED: Self = Self::new("masked", u32::MAX);
+
+    /// Creates a profile called `name` with threshold `threshold`.
+    #[inline]
+    pub const fn new(name: &'static str, threshold: u32) -> Self {
+        Self { name, threshold }
+    }
+
+    /// Creates a profile accepting only interrupts with priority `min` or higher.
+    #[inline]
+    pub const fn critical_only(min: Priority) -> Self {
+        Self::new("rt-critical-only", min.get() - 1)
+    }
+
+    /// Returns the name of the profile.
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the threshold the profile programs.
+    #[inline]
+    pub const fn threshold(&self) -> u32 {
+        self.threshold
+    }
+}
+
+impl Default for ThresholdProfile {
+    fn default() -> Self {
+        Self::NORMAL
+    }
+}
+
+impl Plic {
+    /// Programs the threshold of `context` from `profile`, returning the previous threshold.
+    ///
+    /// See §7.
+    #[inline]
+    pub fn apply_profile(&mut self, ctx: usize, profile: &ThresholdProfile) -> u32 {
+        self.swap_threshold(ctx, profile.threshold)
+    }
+}
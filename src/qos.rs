@@ -0,0 +1,84 @@
+//! Priority bands for device classes.
+//!
+//! Systems with many drivers tend to end up with ad-hoc priorities that collide. [`QosBands`]
+//! splits the implemented priority range into one band per [`QosClass`], from
+//! [`QosClass::Misc`] at the bottom to [`QosClass::Hid`] at the top, and
+//! [`Plic::assign_qos`] gives a source the priority of its class.
+
+use core::num::NonZeroU32;
+use core::ops::RangeInclusive;
+
+use crate::{Plic, Priority};
+
+/// A device class, in ascending order of priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum QosClass {
+    /// Everything else.
+    Misc,
+    /// Block devices.
+    Storage,
+    /// Network interfaces.
+    Network,
+    /// Human input devices, where latency is most visible.
+    Hid,
+}
+
+impl QosClass {
+    /// Number of classes.
+    pub const COUNT: u32 = 4;
+}
+
+/// The priority band of each [`QosClass`] for a given maximum priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct QosBands {
+    max: Priority,
+}
+
+impl QosBands {
+    /// Creates the bands for priorities `1..=max`.
+    ///
+    /// With fewer than four priorities, neighbouring classes share a priority.
+    #[inline]
+    pub const fn new(max: Priority) -> Self {
+        Self { max }
+    }
+
+    /// Creates the bands for the priority range `source` implements, see
+    /// [`Priority::max_for`].
+    pub fn probe(plic: &mut Plic, source: NonZeroU32) -> Option<Self> {
+        Priority::max_for(plic, source).map(Self::new)
+    }
+
+    /// Returns the priorities of `class`.
+    pub const fn band(&self, class: QosClass) -> RangeInclusive<u32> {
+        let (max, i) = (self.max.get() as u64, class as u64);
+        let lo = 1 + i * max / QosClass::COUNT as u64;
+        let hi = (i + 1) * max / QosClass::COUNT as u64;
+        let hi = if hi > lo { hi } else { lo };
+        lo as u32..=hi as u32
+    }
+
+    /// Returns the priority given to sources of `class`, the bottom of its band.
+    pub const fn priority(&self, class: QosClass) -> Priority {
+        match Priority::new(*self.band(class).start()) {
+            Some(priority) => priority,
+            None => Priority::MIN_ACTIVE,
+        }
+    }
+}
+
+impl Plic {
+    /// Sets the priority of interrupt `source` to the priority of `class` within the range the
+    /// source implements.
+    ///
+    /// Returns the priority written, or `None` if the source has no writable priority bits.
+    /// See §4.
+    #[cold]
+    pub fn assign_qos(&mut self, source: NonZeroU32, class: QosClass) -> Option<Priority> {
+        let priority = QosBands::probe(self, source)?.priority(class);
+        self.set_priority(source, priority.get());
+        Some(priority)
+    }
+}
@@ -0,0 +1,134 @@
+//! Fencing off wedged devices.
+//!
+//! A driver that detects its hardware is stuck can [`Plic::quarantine`] the line. The source is
+//! masked everywhere and stays masked: [`Plic::enable`], [`Plic::set_priority`] and the
+//! word-wide enable restores leave it alone, so affinity, balancing and resume paths cannot
+//! bring it back behind the driver's back. Only [`Plic::release`] lifts the quarantine.
+
+use core::num::NonZeroU32;
+use core::sync::atomic::Ordering;
+
+use tock_registers::interfaces::Writeable;
+
+use crate::{Plic, parse_group_and_field};
+
+impl Plic {
+    /// Masks interrupt `source` in every context and keeps it masked until
+    /// [`Plic::release`].
+    ///
+    /// The priority is set to 0, which masks the source in all contexts (§4). With a context
+    /// map installed, the source is also disabled in every connected context; without one the
+    /// implemented contexts are unknown, so the enable bits are left alone.
+    ///
+    /// A source in service stays enabled in the context it was claimed on until it is
+    /// completed there, because the PLIC ignores completions of disabled sources (§9) and the
+    /// gateway would never forward it again.
+    ///
+    /// The quarantine is recorded in the [`PlicState`](crate::PlicState). Without one the
+    /// source is masked once but not kept masked, and [`Plic::is_quarantined`] reports `false`.
+    #[cold]
+    pub fn quarantine(&mut self, source: NonZeroU32) {
+        let (group, field) = parse_group_and_field(source.get() as usize);
+        if let Some(state) = self.state {
+            state.quarantined[group].fetch_or(field.mask << field.shift, Ordering::AcqRel);
+        }
+        // `Plic::set_priority` skips quarantined sources.
+        self.regs().interrupt_priority[source.get() as usize].set(0);
+        let Some(map) = self.context_map else {
+            return;
+        };
+        let claimed_on = self.in_service_context(source);
+        for (ctx, _target) in map.contexts() {
+            #[cfg(feature = "supervisor-only")]
+            if _target.mode == crate::context::Mode::Machine {
+                continue;
+            }
+            if claimed_on != Some(ctx) {
+                self.disable(source, ctx);
+            }
+        }
+    }
+
+    /// Lifts the quarantine of interrupt `source`.
+    ///
+    /// The source stays disabled with priority 0; the driver has to request it again.
+    #[cold]
+    pub fn release(&mut self, source: NonZeroU32) {
+        let (group, field) = parse_group_and_field(source.get() as usize);
+        if let Some(state) = self.state {
+            state.quarantined[group].fetch_and(!(field.mask << field.shift), Ordering::AcqRel);
+        }
+    }
+
+    /// Checks if interrupt `source` is quarantined.
+    #[inline]
+    pub fn is_quarantined(&self, source: NonZeroU32) -> bool {
+        let (group, field) = parse_group_and_field(source.get() as usize);
+        field.read(self.quarantined_word(group)) != 0
+    }
+
+    /// Returns the quarantined-source bits of enable word `group`.
+    #[inline]
+    pub(crate) fn quarantined_word(&self, group: usize) -> u32 {
+        self.state
+            .map_or(0, |state| state.quarantined[group].load(Ordering::Acquire))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PlicError;
+    use crate::context::{ContextMap, ContextTarget};
+    use crate::testing::{host_plic, irq, registers, set_claim};
+
+    static TARGETS: [Option<ContextTarget>; 3] = [
+        Some(ContextTarget::machine(0)),
+        Some(ContextTarget::supervisor(0)),
+        Some(ContextTarget::supervisor(1)),
+    ];
+
+    #[test]
+    fn quarantined_source_is_disabled_once_its_claim_completes() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        plic.set_context_map(ContextMap::new(&TARGETS));
+        plic.set_priority(irq(5), 3);
+        plic.enable(irq(5), 1);
+        plic.enable(irq(5), 2);
+
+        set_claim(&plic, 1, Some(irq(5)));
+        assert_eq!(plic.claim(1), Some(irq(5)));
+        plic.quarantine(irq(5));
+        assert!(plic.is_quarantined(irq(5)));
+        assert_eq!(plic.get_priority(irq(5)), 0);
+        assert!(plic.is_enabled(irq(5), 1));
+        assert!(!plic.is_enabled(irq(5), 2));
+
+        plic.complete(1, irq(5));
+        assert!(!plic.is_enabled(irq(5), 1));
+    }
+
+    #[test]
+    fn quarantined_source_stays_masked_until_released() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        plic.set_context_map(ContextMap::new(&TARGETS));
+
+        plic.quarantine(irq(5));
+        assert_eq!(
+            plic.set_priority_checked(irq(5), 3),
+            Err(PlicError::Quarantined { source: 5 })
+        );
+        plic.set_priority(irq(5), 3);
+        plic.enable(irq(5), 1);
+        assert_eq!(plic.get_priority(irq(5)), 0);
+        assert!(!plic.is_enabled(irq(5), 1));
+
+        plic.release(irq(5));
+        assert!(!plic.is_quarantined(irq(5)));
+        plic.set_priority(irq(5), 3);
+        plic.enable(irq(5), 1);
+        assert_eq!(plic.get_priority(irq(5)), 3);
+        assert!(plic.is_enabled(irq(5), 1));
+    }
+}
@@ -0,0 +1,184 @@
+//! Automatic rate limiting of noisy interrupt sources.
+//!
+//! A [`RateLimiter`] installed as the dispatcher's observer counts claims per source. When a
+//! source is claimed more than `limit` times within `window` clock ticks, the dispatcher masks
+//! it on the spot, which protects the system from stuck level-triggered lines even when they
+//! would starve the timer interrupt. [`RateLimiter::tick`] unmasks it after `cooldown` ticks.
+//!
+//! Throttling is tracked apart from [`Dispatcher::disable_lazy`], so a source its driver
+//! disabled stays masked when the cooldown expires.
+
+use core::num::NonZeroU32;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::Plic;
+use crate::dispatch::{Dispatcher, IrqObserver};
+
+/// A rate-limiting event reported to the [`RateLimiter`] callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RateEvent {
+    /// The source exceeded its rate and was masked.
+    Throttled,
+    /// The cooldown expired and the source was unmasked.
+    Released,
+}
+
+/// Per-source claim rate limiting for sources `0..S`.
+///
+/// Timestamps are truncated to 32 bits, so `window` and `cooldown` must stay below `2^31`
+/// clock ticks.
+pub struct RateLimiter<const S: usize> {
+    limit: u32,
+    window: u32,
+    cooldown: u32,
+    callback: Option<fn(NonZeroU32, RateEvent)>,
+    window_start: [AtomicU32; S],
+    count: [AtomicU32; S],
+    /// Time the cooldown of a throttled source started, plus one; 0 while not throttled.
+    throttled_at: [AtomicU32; S],
+}
+
+impl<const S: usize> RateLimiter<S> {
+    /// Creates a limiter allowing up to `limit` claims per `window` ticks, masking offenders for
+    /// `cooldown` ticks and reporting events to `callback`.
+    pub const fn new(
+        limit: u32,
+        window: u32,
+        cooldown: u32,
+        callback: Option<fn(NonZeroU32, RateEvent)>,
+    ) -> Self {
+        Self {
+            limit,
+            window,
+            cooldown,
+            callback,
+            window_start: [const { AtomicU32::new(0) }; S],
+            count: [const { AtomicU32::new(0) }; S],
+            throttled_at: [const { AtomicU32::new(0) }; S],
+        }
+    }
+
+    /// Returns whether interrupt `source` is currently throttled.
+    pub fn is_throttled(&self, source: NonZeroU32) -> bool {
+        self.throttled_at
+            .get(source.get() as usize)
+            .is_some_and(|t| t.load(Ordering::Acquire) != 0)
+    }
+
+    /// Unmasks every source whose cooldown has expired at time `now`, returning how many.
+    ///
+    /// Call this periodically, e.g. from the kernel tick.
+    pub fn tick<const N: usize>(
+        &self,
+        dispatcher: &Dispatcher<N>,
+        plic: &mut Plic,
+        now: u64,
+    ) -> usize {
+        let now = now as u32;
+        let mut released = 0;
+        for (source, at) in self.throttled_at.iter().enumerate() {
+            let Some(source) = NonZeroU32::new(source as u32) else {
+                continue;
+            };
+            let start = at.load(Ordering::Acquire);
+            if start == 0 || now.wrapping_sub(start - 1) < self.cooldown {
+                continue;
+            }
+            at.store(0, Ordering::Release);
+            self.count[source.get() as usize].store(0, Ordering::Relaxed);
+            dispatcher.unthrottle(plic, source);
+            if let Some(callback) = self.callback {
+                callback(source, RateEvent::Released);
+            }
+            released += 1;
+        }
+        released
+    }
+}
+
+impl<const S: usize> IrqObserver for RateLimiter<S> {
+    fn throttle(&self, source: NonZeroU32, _ctx: usize, now: u64) -> bool {
+        let i = source.get() as usize;
+        let (Some(start), Some(count)) = (self.window_start.get(i), self.count.get(i)) else {
+            return false;
+        };
+        let now = now as u32;
+        if now.wrapping_sub(start.load(Ordering::Relaxed)) >= self.window {
+            start.store(now, Ordering::Relaxed);
+            count.store(0, Ordering::Relaxed);
+        }
+        count.fetch_add(1, Ordering::Relaxed) >= self.limit
+    }
+
+    fn throttled(&self, source: NonZeroU32, _ctx: usize, now: u64) {
+        let i = source.get() as usize;
+        let (Some(count), Some(at)) = (self.count.get(i), self.throttled_at.get(i)) else {
+            return;
+        };
+        // Paired with another observer, that one may have asked for the throttling.
+        if count.load(Ordering::Relaxed) <= self.limit {
+            return;
+        }
+        let now = now as u32;
+        if at.swap(now.wrapping_add(1).max(1), Ordering::AcqRel) == 0
+            && let Some(callback) = self.callback
+        {
+            callback(source, RateEvent::Throttled);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicU64;
+
+    use super::*;
+    use crate::dispatch::IrqReturn;
+    use crate::testing::{host_plic, irq, registers, set_claim};
+
+    #[test]
+    fn noisy_source_is_masked_until_cooldown_expires() {
+        static NOW: AtomicU64 = AtomicU64::new(0);
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+        static EVENTS: AtomicU32 = AtomicU32::new(0);
+        static LIMITER: RateLimiter<8> = RateLimiter::new(2, 100, 50, Some(event));
+        fn event(source: NonZeroU32, event: RateEvent) {
+            assert_eq!(source.get(), 5);
+            let bit = match event {
+                RateEvent::Throttled => 1,
+                RateEvent::Released => 2,
+            };
+            EVENTS.fetch_or(bit, Ordering::Relaxed);
+        }
+        fn handler(_source: NonZeroU32) -> IrqReturn {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            IrqReturn::Handled
+        }
+
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        let mut dispatcher = Dispatcher::<4>::new();
+        dispatcher.set_clock(|| NOW.load(Ordering::Relaxed));
+        dispatcher.set_observer(Some(&LIMITER));
+        dispatcher.register(irq(5), handler).unwrap();
+        plic.set_priority(irq(5), 3);
+
+        set_claim(&plic, 1, Some(irq(5)));
+        for _ in 0..3 {
+            assert!(dispatcher.handle_one(&mut plic, 1));
+        }
+        assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+        assert_eq!(EVENTS.load(Ordering::Relaxed), 1);
+        assert!(LIMITER.is_throttled(irq(5)));
+        assert_eq!(plic.get_priority(irq(5)), 0);
+        assert!(!plic.is_in_service(irq(5)));
+
+        assert_eq!(LIMITER.tick(&dispatcher, &mut plic, 49), 0);
+        assert_eq!(plic.get_priority(irq(5)), 0);
+        assert_eq!(LIMITER.tick(&dispatcher, &mut plic, 50), 1);
+        assert_eq!(EVENTS.load(Ordering::Relaxed), 3);
+        assert!(!LIMITER.is_throttled(irq(5)));
+        assert_eq!(plic.get_priority(irq(5)), 3);
+    }
+}
@@ -0,0 +1,336 @@
+//! Page-granular views of the PLIC register space.
+//!
+//! A kernel may map the globally shared region (priorities, pending and enable bits) and each
+//! context page separately, e.g. giving every hart a private mapping of its own claim page and
+//! keeping the global registers read-only for most of the kernel. [`PlicGlobal`] and
+//! [`PlicContextPage`] each cover one such mapping.
+//!
+//! Unlike [`Plic`], these views keep no software state, so there is no in-service
+//! tracking or strict-mode checking.
+//!
+//! [`Plic::split`] instead splits a [`Plic`] by ownership: each [`PlicContext`] owns the
+//! threshold, claim/complete and enable registers of one context and can move to the hart
+//! serving it, while [`PlicShared`] keeps the global operations available to all harts.
+
+use core::num::NonZeroU32;
+use core::ops::Deref;
+use core::ptr::NonNull;
+
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable, Writeable},
+    register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+use crate::{
+    CONTEXT_NUM, ContextLocal, PENDING_BASE, Plic, PlicError, SOURCE_NUM, U32_BITS,
+    parse_group_and_field,
+};
+
+register_structs! {
+    /// The globally shared PLIC registers below the context pages.
+    pub GlobalRegs {
+        /// Interrupt Source Priority #0 to #1023
+        (0x000000 => interrupt_priority: [ReadWrite<u32>; SOURCE_NUM]),
+        /// Interrupt Pending Bit of Interrupt Source #0 to #1023
+        (0x001000 => interrupt_pending: [ReadOnly<u32>; SOURCE_NUM / U32_BITS]),
+        (0x001080 => _reserved_0),
+        /// Interrupt Enable Bit of Interrupt Source #0 to #1023 for 15872 contexts
+        (0x002000 => interrupt_enable: [[ReadWrite<u32>; SOURCE_NUM / U32_BITS]; CONTEXT_NUM]),
+        (0x1F2000 => @END),
+    }
+}
+
+const _: () = assert!(core::mem::offset_of!(GlobalRegs, interrupt_pending) == PENDING_BASE);
+
+/// The priority, pending and enable registers of a PLIC, mapped on their own.
+pub struct PlicGlobal {
+    base: NonNull<GlobalRegs>,
+}
+
+unsafe impl Send for PlicGlobal {}
+unsafe impl Sync for PlicGlobal {}
+
+impl PlicGlobal {
+    /// Create a view of the global registers at `base`, the PLIC base address.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be a valid pointer to the first 0x1F2000 bytes of PLIC registers, unique
+    /// among views allowed to write them.
+    #[inline]
+    pub const unsafe fn new(base: NonNull<GlobalRegs>) -> Self {
+        Self { base }
+    }
+
+    #[inline]
+    const fn regs(&self) -> &GlobalRegs {
+        unsafe { self.base.as_ref() }
+    }
+
+    /// Sets priority for interrupt `source` to `value`.
+    ///
+    /// See §4.
+    #[inline]
+    pub fn set_priority(&mut self, source: NonZeroU32, value: u32) {
+        self.regs().interrupt_priority[source.get() as usize].set(value);
+    }
+
+    /// Gets priority for interrupt `source`.
+    ///
+    /// See §4.
+    #[inline]
+    pub fn get_priority(&self, source: NonZeroU32) -> u32 {
+        self.regs().interrupt_priority[source.get() as usize].get()
+    }
+
+    /// Check if interrupt `source` is pending.
+    ///
+    /// See §5.
+    #[inline]
+    pub fn is_pending(&self, source: NonZeroU32) -> bool {
+        let (group, field) = parse_group_and_field(source.get() as usize);
+        self.regs().interrupt_pending[group].read(field) != 0
+    }
+
+    /// Enable interrupt `source` in `context`.
+    ///
+    /// See §6.
+    #[inline]
+    pub fn enable(&mut self, source: NonZeroU32, ctx: usize) {
+        let (group, field) = parse_group_and_field(source.get() as usize);
+        self.regs().interrupt_enable[ctx][group].modify(field.val(1));
+    }
+
+    /// Disable interrupt `source` in `context`.
+    ///
+    /// See §6.
+    #[inline]
+    pub fn disable(&mut self, source: NonZeroU32, ctx: usize) {
+        let (group, field) = parse_group_and_field(source.get() as usize);
+        self.regs().interrupt_enable[ctx][group].modify(field.val(0));
+    }
+
+    /// Check if interrupt `source` is enabled in `context`.
+    ///
+    /// See §6.
+    #[inline]
+    pub fn is_enabled(&self, source: NonZeroU32, ctx: usize) -> bool {
+        let (group, field) = parse_group_and_field(source.get() as usize);
+        self.regs().interrupt_enable[ctx][group].read(field) != 0
+    }
+}
+
+/// The threshold and claim/complete registers of one context, mapped on their own.
+pub struct PlicContextPage {
+    base: NonNull<ContextLocal>,
+}
+
+unsafe impl Send for PlicContextPage {}
+unsafe impl Sync for PlicContextPage {}
+
+impl PlicContextPage {
+    /// Create a view of the context page at `base`.
+    ///
+    /// The page of context `ctx` lies at [`Plic::context_page`](crate::Plic::context_page) from
+    /// the PLIC base address.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be a unique valid pointer to a 4 KiB PLIC context page.
+    #[inline]
+    pub const unsafe fn new(base: NonNull<ContextLocal>) -> Self {
+        Self { base }
+    }
+
+    #[inline]
+    const fn regs(&self) -> &ContextLocal {
+        unsafe { self.base.as_ref() }
+    }
+
+    /// Get interrupt threshold of the context.
+    ///
+    /// See §7.
+    #[inline]
+    pub fn get_threshold(&self) -> u32 {
+        self.regs().priority_threshold.get()
+    }
+
+    /// Set interrupt threshold of the context to `value`.
+    ///
+    /// See §7.
+    #[inline]
+    pub fn set_threshold(&mut self, value: u32) {
+        self.regs().priority_threshold.set(value);
+    }
+
+    /// Claim an interrupt in the context, returning its source.
+    ///
+    /// See §8.
+    #[inline]
+    pub fn claim(&mut self) -> Option<NonZeroU32> {
+        NonZeroU32::new(self.regs().interrupt_claim_complete.get())
+    }
+
+    /// Mark that interrupt `source` is completed in the context.
+    ///
+    /// See §9.
+    #[inline]
+    pub fn complete(&mut self, source: NonZeroU32) {
+        self.regs().interrupt_claim_complete.set(source.get());
+    }
+}
+
+/// The global operations of a [`Plic`] split with [`Plic::split`], shareable between harts.
+///
+/// It dereferences to the PLIC for the read-only operations.
+#[derive(Clone, Copy)]
+pub struct PlicShared<'p> {
+    plic: &'p Plic,
+}
+
+impl PlicShared<'_> {
+    /// Sets priority for interrupt `source` to `value`, see [`Plic::set_priority`].
+    ///
+    /// A priority is a single register write, so harts may do this concurrently.
+    #[inline]
+    pub fn set_priority(&self, source: NonZeroU32, value: u32) {
+        _ = self.plic.write_priority(source, value);
+    }
+}
+
+impl Deref for PlicShared<'_> {
+    type Target = Plic;
+
+    #[inline]
+    fn deref(&self) -> &Plic {
+        self.plic
+    }
+}
+
+/// The per-context operations of one context of a [`Plic`] split with [`Plic::split`].
+///
+/// The enable words of a context are only written through its handle, so their
+/// read-modify-write cycles cannot race with other harts. With the `critical-section`
+/// feature each cycle additionally runs in a critical section, serializing it against every
+/// other enable write made through a shared reference to the PLIC.
+pub struct PlicContext<'p> {
+    plic: &'p Plic,
+    ctx: usize,
+}
+
+impl PlicContext<'_> {
+    /// Returns the context index.
+    #[inline]
+    pub const fn context(&self) -> usize {
+        self.ctx
+    }
+
+    /// Enable interrupt `source` in the context, see [`Plic::enable`].
+    #[inline]
+    pub fn enable(&mut self, source: NonZeroU32) {
+        self.plic.write_enable(source, self.ctx, true);
+    }
+
+    /// Disable interrupt `source` in the context, see [`Plic::disable`].
+    #[inline]
+    pub fn disable(&mut self, source: NonZeroU32) {
+        self.plic.write_enable(source, self.ctx, false);
+    }
+
+    /// Check if interrupt `source` is enabled in the context, see [`Plic::is_enabled`].
+    #[inline]
+    pub fn is_enabled(&self, source: NonZeroU32) -> bool {
+        self.plic.is_enabled(source, self.ctx)
+    }
+
+    /// Get interrupt threshold of the context, see [`Plic::get_threshold`].
+    #[inline]
+    pub fn get_threshold(&self) -> u32 {
+        self.plic.get_threshold(self.ctx)
+    }
+
+    /// Set interrupt threshold of the context to `value`, see [`Plic::set_threshold`].
+    #[inline]
+    pub fn set_threshold(&mut self, value: u32) {
+        self.plic.write_threshold(self.ctx, value);
+    }
+
+    /// Claim an interrupt in the context, see [`Plic::claim`].
+    #[inline]
+    pub fn claim(&mut self) -> Option<NonZeroU32> {
+        self.plic.strict_initialized(self.ctx);
+        // SAFETY: `Plic::split` only hands out contexts below `CONTEXT_NUM`.
+        unsafe { self.plic.claim_unchecked(self.ctx) }
+    }
+
+    /// Mark that interrupt `source` is completed in the context, see [`Plic::complete`].
+    #[inline]
+    pub fn complete(&mut self, source: NonZeroU32) {
+        self.plic.write_complete(self.ctx, source);
+    }
+}
+
+impl Plic {
+    /// Splits the PLIC into one handle per context of `contexts` and a handle for the global
+    /// operations, so an SMP kernel can give each hart its own context without a lock.
+    ///
+    /// Fails with [`PlicError::InvalidContext`] if a context is rejected by
+    /// [`Plic::context_handle`] or listed twice. Operations touching several contexts, e.g.
+    /// [`Plic::clone_enables`], need the PLIC back, i.e. the handles dropped.
+    #[cold]
+    pub fn split<const C: usize>(
+        &mut self,
+        contexts: [usize; C],
+    ) -> Result<(PlicShared<'_>, [PlicContext<'_>; C]), PlicError> {
+        for (i, &ctx) in contexts.iter().enumerate() {
+            self.context_handle(ctx)?;
+            if contexts[..i].contains(&ctx) {
+                return Err(PlicError::InvalidContext { context: ctx });
+            }
+        }
+        let plic = &*self;
+        Ok((
+            PlicShared { plic },
+            contexts.map(|ctx| PlicContext { plic, ctx }),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CONTEXT_NUM;
+    use crate::testing::{host_plic, irq, registers, set_claim};
+
+    #[test]
+    fn split_rejects_duplicate_and_invalid_contexts() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, false);
+
+        let invalid = |context| Some(PlicError::InvalidContext { context });
+        assert_eq!(plic.split([1, 2, 1]).err(), invalid(1));
+        assert_eq!(plic.split([1, CONTEXT_NUM]).err(), invalid(CONTEXT_NUM));
+    }
+
+    #[test]
+    fn context_handles_own_their_context() {
+        let mut memory = registers();
+        let mut plic = host_plic(&mut memory, true);
+        set_claim(&plic, 2, Some(irq(5)));
+
+        let (shared, [mut first, mut second]) = plic.split([1, 2]).unwrap();
+        shared.set_priority(irq(5), 3);
+        first.enable(irq(5));
+        first.set_threshold(1);
+        second.set_threshold(2);
+        assert_eq!(second.claim(), Some(irq(5)));
+        assert_eq!(shared.in_service_context(irq(5)), Some(2));
+        second.complete(irq(5));
+        assert!(!shared.is_in_service(irq(5)));
+
+        assert_eq!(plic.get_priority(irq(5)), 3);
+        assert!(plic.is_enabled(irq(5), 1) && !plic.is_enabled(irq(5), 2));
+        assert_eq!((plic.get_threshold(1), plic.get_threshold(2)), (1, 2));
+    }
+}
@@ -0,0 +1,112 @@
+//! Backoff for contexts that keep taking interrupts with nothing to claim.
+//!
+//! A glitchy level-triggered source can raise the external interrupt of a hart and drop it
+//! again before the claim, so the hart traps over and over for nothing. A [`SpuriousBackoff`]
+//! counts such empty claims per context and, after `limit` in a row, raises the context's
+//! threshold for `cooldown` clock ticks to break the livelock.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::Plic;
+
+/// A backoff event reported to the [`SpuriousBackoff`] callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpuriousEvent {
+    /// The context saw `limit` empty claims in a row and its threshold was raised.
+    BackedOff,
+    /// The cooldown expired and the previous threshold was restored.
+    Restored,
+}
+
+/// Spurious-claim accounting for contexts `0..C`.
+///
+/// The callback is invoked once per backoff episode, so it can log without further rate
+/// limiting. Timestamps are truncated to 32 bits, so `cooldown` must stay below `2^31` ticks.
+pub struct SpuriousBackoff<const C: usize> {
+    limit: u32,
+    raised: u32,
+    cooldown: u32,
+    callback: Option<fn(usize, SpuriousEvent)>,
+    streak: [AtomicU32; C],
+    saved: [AtomicU32; C],
+    /// Time the backoff of a context started, plus one; 0 while not backed off.
+    backoff_at: [AtomicU32; C],
+}
+
+impl<const C: usize> SpuriousBackoff<C> {
+    /// Creates a policy raising the threshold to `raised` for `cooldown` ticks after `limit`
+    /// empty claims in a row, reporting events to `callback`.
+    pub const fn new(
+        limit: u32,
+        raised: u32,
+        cooldown: u32,
+        callback: Option<fn(usize, SpuriousEvent)>,
+    ) -> Self {
+        Self {
+            limit,
+            raised,
+            cooldown,
+            callback,
+            streak: [const { AtomicU32::new(0) }; C],
+            saved: [const { AtomicU32::new(0) }; C],
+            backoff_at: [const { AtomicU32::new(0) }; C],
+        }
+    }
+
+    /// Records the outcome of an external interrupt trap on `context` at time `now`.
+    ///
+    /// `claimed` is whether the trap claimed anything, e.g. the result of
+    /// [`Dispatcher::handle_one`](crate::dispatch::Dispatcher::handle_one). Returns whether the
+    /// context was backed off by this call. Contexts beyond `C` are ignored.
+    pub fn record(&self, plic: &mut Plic, ctx: usize, claimed: bool, now: u64) -> bool {
+        let (Some(streak), Some(at)) = (self.streak.get(ctx), self.backoff_at.get(ctx)) else {
+            return false;
+        };
+        if claimed {
+            streak.store(0, Ordering::Relaxed);
+            return false;
+        }
+        if streak.fetch_add(1, Ordering::Relaxed) + 1 < self.limit
+            || at.load(Ordering::Acquire) != 0
+        {
+            return false;
+        }
+        self.saved[ctx].store(plic.swap_threshold(ctx, self.raised), Ordering::Relaxed);
+        at.store((now as u32).wrapping_add(1).max(1), Ordering::Release);
+        if let Some(callback) = self.callback {
+            callback(ctx, SpuriousEvent::BackedOff);
+        }
+        true
+    }
+
+    /// Returns whether `context` is currently backed off.
+    pub fn is_backed_off(&self, ctx: usize) -> bool {
+        self.backoff_at
+            .get(ctx)
+            .is_some_and(|t| t.load(Ordering::Acquire) != 0)
+    }
+
+    /// Restores the threshold of every context whose cooldown has expired at time `now`,
+    /// returning how many.
+    ///
+    /// Call this periodically, e.g. from the kernel tick.
+    pub fn tick(&self, plic: &mut Plic, now: u64) -> usize {
+        let now = now as u32;
+        let mut restored = 0;
+        for (ctx, at) in self.backoff_at.iter().enumerate() {
+            let start = at.load(Ordering::Acquire);
+            if start == 0 || now.wrapping_sub(start - 1) < self.cooldown {
+                continue;
+            }
+            plic.set_threshold(ctx, self.saved[ctx].load(Ordering::Relaxed));
+            self.streak[ctx].store(0, Ordering::Relaxed);
+            at.store(0, Ordering::Release);
+            if let Some(callback) = self.callback {
+                callback(ctx, SpuriousEvent::Restored);
+            }
+            restored += 1;
+        }
+        restored
+    }
+}
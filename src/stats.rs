@@ -0,0 +1,260 @@
+//! Interrupt statistics collected from [`Dispatcher`](crate::dispatch::Dispatcher) events.
+
+use core::num::NonZeroU32;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::dispatch::IrqObserver;
+
+/// A histogram with power-of-two buckets, filled without allocation.
+///
+/// Bucket 0 counts values `0..2`, bucket `i > 0` counts values in `2^i..2^(i+1)`.
+pub struct LatencyHistogram {
+    buckets: [AtomicU32; Self::BUCKETS],
+}
+
+impl LatencyHistogram {
+    /// Number of buckets.
+    pub const BUCKETS: usize = 32;
+
+    /// Creates an empty histogram.
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU32::new(0) }; Self::BUCKETS],
+        }
+    }
+
+    /// Returns the bucket `value` falls into.
+    #[inline]
+    pub const fn bucket_of(value: u32) -> usize {
+        (value | 1).ilog2() as usize
+    }
+
+    /// Counts one occurrence of `value`.
+    #[inline]
+    pub fn record(&self, value: u32) {
+        self.buckets[Self::bucket_of(value)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns a copy of all bucket counts.
+    pub fn buckets(&self) -> [u32; Self::BUCKETS] {
+        core::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed))
+    }
+
+    /// Resets all buckets to 0.
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Per-source interrupt statistics for sources `0..S` and contexts `0..C`.
+///
+/// Register it with [`Dispatcher::set_observer`](crate::dispatch::Dispatcher::set_observer).
+/// It counts claims per source and context, and records claim-to-complete latencies per source
+/// in clock ticks. Events for sources or contexts out of range are ignored.
+pub struct IrqStats<const S: usize, const C: usize> {
+    claims: [[AtomicU32; C]; S],
+    /// Low 32 bits of the last claim timestamp of each source.
+    claimed_at: [AtomicU32; S],
+    latency: [LatencyHistogram; S],
+}
+
+impl<const S: usize, const C: usize> IrqStats<S, C> {
+    /// Creates empty statistics.
+    pub const fn new() -> Self {
+        Self {
+            claims: [const { [const { AtomicU32::new(0) }; C] }; S],
+            claimed_at: [const { AtomicU32::new(0) }; S],
+            latency: [const { LatencyHistogram::new() }; S],
+        }
+    }
+
+    /// Returns how often interrupt `source` was claimed in `context`.
+    pub fn claims(&self, source: NonZeroU32, ctx: usize) -> u32 {
+        self.claims
+            .get(source.get() as usize)
+            .and_then(|c| c.get(ctx))
+            .map_or(0, |n| n.load(Ordering::Relaxed))
+    }
+
+    /// Returns the claim-to-complete latency histogram of interrupt `source`.
+    pub fn latency(&self, source: NonZeroU32) -> Option<&LatencyHistogram> {
+        self.latency.get(source.get() as usize)
+    }
+
+    /// Iterates over the sources claimed at least once, in ascending order, for rendering a
+    /// `/proc/interrupts`-style table.
+    ///
+    /// `name` supplies the device names, e.g.
+    /// [`Dispatcher::name`](crate::dispatch::Dispatcher::name).
+    pub fn iter<'a, 'n: 'a>(
+        &'a self,
+        name: impl Fn(NonZeroU32) -> Option<&'n str> + 'a,
+    ) -> impl Iterator<Item = IrqStat<'n, C>> + 'a {
+        (1..S as u32)
+            .filter_map(NonZeroU32::new)
+            .filter_map(move |source| {
+                let i = source.get() as usize;
+                let per_context: [u32; C] =
+                    core::array::from_fn(|ctx| self.claims[i][ctx].load(Ordering::Relaxed));
+                per_context.iter().any(|&n| n != 0).then(|| IrqStat {
+                    source,
+                    name: name(source),
+                    per_context,
+                    last_seen: self.claimed_at[i].load(Ordering::Relaxed),
+                })
+            })
+    }
+
+    /// Resets all counters, histograms and last claim times.
+    pub fn reset(&self) {
+        for count in self.claims.iter().flatten() {
+            count.store(0, Ordering::Relaxed);
+        }
+        for at in &self.claimed_at {
+            at.store(0, Ordering::Relaxed);
+        }
+        for histogram in &self.latency {
+            histogram.reset();
+        }
+    }
+}
+
+/// One row of [`IrqStats::iter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IrqStat<'n, const C: usize> {
+    /// The interrupt source.
+    pub source: NonZeroU32,
+    /// The device name, if known.
+    pub name: Option<&'n str>,
+    /// Claims of the source in each context.
+    pub per_context: [u32; C],
+    /// Low 32 bits of the clock at the last claim.
+    pub last_seen: u32,
+}
+
+impl<const S: usize, const C: usize> Default for IrqStats<S, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const S: usize, const C: usize> IrqObserver for IrqStats<S, C> {
+    fn claimed(&self, source: NonZeroU32, ctx: usize, now: u64) {
+        let source = source.get() as usize;
+        if let Some(count) = self.claims.get(source).and_then(|c| c.get(ctx)) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(at) = self.claimed_at.get(source) {
+            at.store(now as u32, Ordering::Relaxed);
+        }
+    }
+
+    fn completed(&self, source: NonZeroU32, _ctx: usize, now: u64) {
+        let source = source.get() as usize;
+        if let (Some(at), Some(histogram)) = (self.claimed_at.get(source), self.latency.get(source))
+        {
+            histogram.record((now as u32).wrapping_sub(at.load(Ordering::Relaxed)));
+        }
+    }
+}
+
+/// How the claims of one context were spread over sources, see [`FairnessMonitor::report`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FairnessReport {
+    /// The context.
+    pub context: usize,
+    /// Claims in the context during the window.
+    pub total: u32,
+    /// The most claimed source and its claims, if anything was claimed.
+    pub top: Option<(NonZeroU32, u32)>,
+}
+
+impl FairnessReport {
+    /// Returns the share of the most claimed source in percent, 0 if nothing was claimed.
+    pub fn top_share_percent(&self) -> u32 {
+        match self.top {
+            Some((_, claims)) if self.total != 0 => {
+                (claims as u64 * 100 / self.total as u64) as u32
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Per-context claim accounting over a window, for sources `0..S` and contexts `0..C`.
+///
+/// Installed as an observer, it counts claims per source and context until
+/// [`FairnessMonitor::reset_window`] starts a new window. Its reports show a single device
+/// monopolizing a hart even when overall interrupt rates look fine.
+pub struct FairnessMonitor<const S: usize, const C: usize> {
+    window: [[AtomicU32; C]; S],
+}
+
+impl<const S: usize, const C: usize> FairnessMonitor<S, C> {
+    /// Creates a monitor with an empty window.
+    pub const fn new() -> Self {
+        Self {
+            window: [const { [const { AtomicU32::new(0) }; C] }; S],
+        }
+    }
+
+    /// Returns the claims of interrupt `source` in `context` during the current window.
+    pub fn claims(&self, source: NonZeroU32, ctx: usize) -> u32 {
+        self.window
+            .get(source.get() as usize)
+            .and_then(|c| c.get(ctx))
+            .map_or(0, |n| n.load(Ordering::Relaxed))
+    }
+
+    /// Summarizes the current window of `context`.
+    pub fn report(&self, ctx: usize) -> FairnessReport {
+        let mut report = FairnessReport {
+            context: ctx,
+            total: 0,
+            top: None,
+        };
+        for source in (1..S as u32).filter_map(NonZeroU32::new) {
+            let claims = self.claims(source, ctx);
+            report.total = report.total.saturating_add(claims);
+            if claims > report.top.map_or(0, |(_, n)| n) {
+                report.top = Some((source, claims));
+            }
+        }
+        report
+    }
+
+    /// Starts a new window, clearing all counts.
+    pub fn reset_window(&self) {
+        for count in self.window.iter().flatten() {
+            count.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<const S: usize, const C: usize> Default for FairnessMonitor<S, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const S: usize, const C: usize> IrqObserver for FairnessMonitor<S, C> {
+    fn claimed(&self, source: NonZeroU32, ctx: usize, _now: u64) {
+        if let Some(count) = self
+            .window
+            .get(source.get() as usize)
+            .and_then(|c| c.get(ctx))
+        {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
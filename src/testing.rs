@@ -0,0 +1,59 @@
+//! Fixtures shared by the unit tests.
+//!
+//! The PLIC under test lives in plain zeroed memory, so every register keeps whatever was
+//! last written to it: there is no WARL masking, and the claim register returns the same
+//! source until a test changes it with [`set_claim`].
+
+extern crate std;
+
+use core::num::NonZeroU32;
+use core::ptr::NonNull;
+use std::boxed::Box;
+use std::vec::Vec;
+
+use crate::{CONTEXT_BASE, CONTEXT_STRIDE, PENDING_BASE, PLICRegs, Plic, PlicState, U32_BITS};
+
+pub(crate) fn irq(source: u32) -> NonZeroU32 {
+    NonZeroU32::new(source).unwrap()
+}
+
+/// Zeroed memory standing in for the PLIC registers.
+pub(crate) fn registers() -> Vec<u32> {
+    std::vec![0; size_of::<PLICRegs>() / size_of::<u32>()]
+}
+
+/// A PLIC on `memory`, with a leaked [`PlicState`] if `state` is set.
+///
+/// Contexts 0 to 3 are initialized, so that they can claim with the `strict` feature.
+pub(crate) fn host_plic(memory: &mut [u32], state: bool) -> Plic {
+    let base = NonNull::new(memory.as_mut_ptr())
+        .unwrap()
+        .cast::<PLICRegs>();
+    let mut plic = unsafe { Plic::new(base) };
+    if state {
+        plic.set_state(Box::leak(Box::new(PlicState::new())));
+    }
+    for ctx in 0..4 {
+        plic.init_by_context(ctx);
+    }
+    plic
+}
+
+/// Writes `value` to the register at `offset`, including read-only ones.
+fn poke(plic: &Plic, offset: usize, value: u32) {
+    let base = core::ptr::from_ref(plic.regs()).cast::<u32>().cast_mut();
+    unsafe { base.add(offset / size_of::<u32>()).write_volatile(value) };
+}
+
+/// Makes claims in `context` return `source`, or nothing for `None`.
+pub(crate) fn set_claim(plic: &Plic, ctx: usize, source: Option<NonZeroU32>) {
+    let offset = CONTEXT_BASE + ctx * CONTEXT_STRIDE + size_of::<u32>();
+    poke(plic, offset, source.map_or(0, NonZeroU32::get));
+}
+
+/// Sets the pending bit of `source`, leaving the rest of its word clear.
+pub(crate) fn set_pending(plic: &Plic, source: NonZeroU32) {
+    let source = source.get() as usize;
+    let offset = PENDING_BASE + source / U32_BITS * size_of::<u32>();
+    poke(plic, offset, 1 << (source % U32_BITS));
+}
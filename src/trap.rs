@@ -0,0 +1,145 @@
+//! Glue between the external interrupt trap and the [`Dispatcher`].
+//!
+//! A kernel calls [`handle_external_trap`] from the branch of its trap handler taken when
+//! [`external_interrupt_mode`] recognises the cause, passing the [`HartContexts`] of the hart
+//! it looked up at boot. It claims, dispatches and completes until nothing is pending.
+//! [`handle_trap`] does both steps for the interrupt branch, finding the current hart in a
+//! [`TrapContexts`] table as told by a [`HartId`]. Neither searches the context map on the
+//! trap path.
+//!
+//! Naked trap vectors are left out: saving registers and switching stacks depend on the
+//! trap frame layout of the kernel, which calls in here once its frame is set up.
+
+use crate::Plic;
+use crate::context::{HartContexts, Mode};
+use crate::dispatch::Dispatcher;
+
+/// Interrupt bit of `scause` and `mcause`.
+const CAUSE_INTERRUPT: usize = 1 << (usize::BITS - 1);
+/// Exception code of a supervisor external interrupt.
+const SUPERVISOR_EXTERNAL: usize = 9;
+/// Exception code of a machine external interrupt.
+#[cfg(not(feature = "supervisor-only"))]
+const MACHINE_EXTERNAL: usize = 11;
+
+/// Returns the mode whose external interrupt `cause`, a `scause` or `mcause` value, reports.
+///
+/// With the `supervisor-only` feature, only supervisor external interrupts are recognised.
+#[inline]
+pub const fn external_interrupt_mode(cause: usize) -> Option<Mode> {
+    match cause {
+        c if c == CAUSE_INTERRUPT | SUPERVISOR_EXTERNAL => Some(Mode::Supervisor),
+        #[cfg(not(feature = "supervisor-only"))]
+        c if c == CAUSE_INTERRUPT | MACHINE_EXTERNAL => Some(Mode::Machine),
+        _ => None,
+    }
+}
+
+/// Where the id of the current hart comes from.
+#[derive(Debug, Clone, Copy)]
+pub enum HartId {
+    /// The `mhartid` CSR, readable in machine mode only.
+    #[cfg(all(
+        any(target_arch = "riscv32", target_arch = "riscv64"),
+        not(feature = "supervisor-only")
+    ))]
+    Mhartid,
+    /// The `tp` register, for kernels keeping the hart id there while in the kernel.
+    #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+    Tp,
+    /// A kernel function, e.g. one reading a per-CPU variable.
+    With(fn() -> usize),
+}
+
+impl HartId {
+    /// Returns the id of the current hart.
+    #[inline]
+    pub fn current(self) -> usize {
+        match self {
+            #[cfg(all(
+                any(target_arch = "riscv32", target_arch = "riscv64"),
+                not(feature = "supervisor-only")
+            ))]
+            Self::Mhartid => {
+                let id: usize;
+                unsafe {
+                    core::arch::asm!("csrr {0}, mhartid", out(reg) id, options(nomem, nostack))
+                };
+                id
+            }
+            #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+            Self::Tp => {
+                let id: usize;
+                unsafe { core::arch::asm!("mv {0}, tp", out(reg) id, options(nomem, nostack)) };
+                id
+            }
+            Self::With(f) => f(),
+        }
+    }
+}
+
+/// The contexts of harts `0..H`, looked up once at boot for [`handle_trap`].
+#[derive(Debug, Clone, Copy)]
+pub struct TrapContexts<const H: usize> {
+    hart: HartId,
+    contexts: [HartContexts; H],
+}
+
+impl<const H: usize> TrapContexts<H> {
+    /// Looks up the contexts of harts `0..H` in the map installed with
+    /// [`Plic::set_context_map`], to be found through `hart` on each trap.
+    #[cold]
+    pub fn new(plic: &Plic, hart: HartId) -> Self {
+        Self {
+            hart,
+            contexts: core::array::from_fn(|hart| plic.hart(hart)),
+        }
+    }
+
+    /// Returns the contexts of the current hart, or `None` if its id is not below `H`.
+    #[inline]
+    pub fn current(&self) -> Option<HartContexts> {
+        self.contexts.get(self.hart.current()).copied()
+    }
+}
+
+/// Handles every interrupt pending in the `mode` context of the hart with `contexts`.
+///
+/// `contexts` comes from [`Plic::hart`], typically once at boot into per-CPU data; if the
+/// hart has no context for `mode`, nothing is claimed. Returns the number of interrupts
+/// claimed.
+#[inline]
+pub fn handle_external_trap<const N: usize>(
+    plic: &mut Plic,
+    dispatcher: &Dispatcher<N>,
+    contexts: HartContexts,
+    mode: Mode,
+) -> usize {
+    let Some(ctx) = contexts.get(mode) else {
+        return 0;
+    };
+    let mut n = 0;
+    while dispatcher.handle_one(plic, ctx) {
+        n += 1;
+    }
+    n
+}
+
+/// Handles the interrupt branch of a trap with cause `cause`, a `scause` or `mcause` value.
+///
+/// Returns `None` if `cause` is not an external interrupt, and otherwise the number of
+/// interrupts claimed by [`handle_external_trap`] for the current hart, found in `harts`;
+/// nothing is claimed for a hart beyond the table.
+#[inline]
+pub fn handle_trap<const N: usize, const H: usize>(
+    plic: &mut Plic,
+    dispatcher: &Dispatcher<N>,
+    cause: usize,
+    harts: &TrapContexts<H>,
+) -> Option<usize> {
+    let mode = external_interrupt_mode(cause)?;
+    let Some(contexts) = harts.current() else {
+        return Some(0);
+    };
+    Some(handle_external_trap(plic, dispatcher, contexts, mode))
+}
@@ -0,0 +1,116 @@
+//! Runtime priority tuning driven by interrupt statistics.
+//!
+//! A [`PriorityTuner`] periodically feeds the observed behavior of every active source to a
+//! pluggable [`PriorityPolicy`] and applies the priorities it proposes, clamped to a per-source
+//! floor so critical sources are never demoted below it.
+
+use core::num::NonZeroU32;
+
+use crate::stats::{IrqStats, LatencyHistogram};
+use crate::{Plic, Priority};
+
+/// What a source did since the previous [`PriorityTuner::tick`].
+pub struct SourceSample<'a> {
+    /// Claims across all contexts since the previous tick.
+    pub claims: u32,
+    /// Claim-to-complete latency histogram since the statistics were last reset.
+    pub latency: &'a LatencyHistogram,
+}
+
+/// A policy deciding source priorities from observations.
+pub trait PriorityPolicy {
+    /// Returns the new priority for `source`, currently at `current`, or `None` to keep it.
+    fn adjust(
+        &mut self,
+        source: NonZeroU32,
+        current: Priority,
+        sample: &SourceSample<'_>,
+    ) -> Option<Priority>;
+}
+
+/// A policy demoting sources that are claimed more than `limit` times per tick by one priority
+/// level per tick.
+#[derive(Debug, Clone, Copy)]
+pub struct DemoteChatty {
+    /// Claims per tick above which a source is demoted.
+    pub limit: u32,
+}
+
+impl PriorityPolicy for DemoteChatty {
+    fn adjust(
+        &mut self,
+        _source: NonZeroU32,
+        current: Priority,
+        sample: &SourceSample<'_>,
+    ) -> Option<Priority> {
+        if sample.claims > self.limit {
+            Priority::new(current.get() - 1)
+        } else {
+            None
+        }
+    }
+}
+
+/// Applies a [`PriorityPolicy`] to sources `1..S`, using statistics for `C` contexts.
+pub struct PriorityTuner<const S: usize, const C: usize> {
+    last_claims: [u32; S],
+    floors: [Option<Priority>; S],
+}
+
+impl<const S: usize, const C: usize> PriorityTuner<S, C> {
+    /// Creates a tuner with no floors, i.e. every source may go down to
+    /// [`Priority::MIN_ACTIVE`].
+    pub const fn new() -> Self {
+        Self {
+            last_claims: [0; S],
+            floors: [None; S],
+        }
+    }
+
+    /// Sets the lowest priority the tuner may give interrupt `source`.
+    pub fn set_floor(&mut self, source: NonZeroU32, floor: Priority) {
+        self.floors[source.get() as usize] = Some(floor);
+    }
+
+    /// Runs `policy` over every source with a non-zero priority and applies its proposals.
+    ///
+    /// Proposals below the source's floor are raised to the floor. Sources with priority 0 are
+    /// disabled and left alone. Returns the number of priorities changed.
+    pub fn tick(
+        &mut self,
+        plic: &mut Plic,
+        stats: &IrqStats<S, C>,
+        policy: &mut impl PriorityPolicy,
+    ) -> usize {
+        let mut changed = 0;
+        for source in (1..S as u32).filter_map(NonZeroU32::new) {
+            let i = source.get() as usize;
+            let total = (0..C).fold(0u32, |n, ctx| n.wrapping_add(stats.claims(source, ctx)));
+            let claims = total.wrapping_sub(self.last_claims[i]);
+            self.last_claims[i] = total;
+
+            let (Some(current), Some(latency)) = (
+                Priority::new(plic.get_priority(source)),
+                stats.latency(source),
+            ) else {
+                continue;
+            };
+            let sample = SourceSample { claims, latency };
+            let Some(proposed) = policy.adjust(source, current, &sample) else {
+                continue;
+            };
+            let new = self.floors[i].map_or(proposed, |floor| proposed.max(floor));
+            if new != current {
+                plic.set_priority(source, new.get());
+                changed += 1;
+            }
+        }
+        changed
+    }
+}
+
+impl<const S: usize, const C: usize> Default for PriorityTuner<S, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
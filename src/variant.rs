@@ -0,0 +1,253 @@
+//! Vendor extensions of the baseline PLIC.
+//!
+//! Some shipping PLICs need extra set-up steps or have registers beyond the specification.
+//! A [`PlicVariant`] installed with [`Plic::set_variant`] hooks these into construction and
+//! [`Plic::init_by_context`], and implementations expose their extra registers:
+//!
+//! - [`Generic`]: the baseline specification, nothing extra.
+//! - [`THead`]: the T-HEAD C906/C910 PLIC (`thead,c900-plic`), whose `PLIC_CTRL` register
+//!   decides whether supervisor mode may access the PLIC.
+//! - [`Andes`]: the Andes NCEPLIC100 (`andestech,nceplic100`), with preemptive and vectored
+//!   modes and per-source trigger types.
+//!
+//! Vendor registers only live in windows the specification reserves: the priority slot of
+//! source 0, the gap between the pending and enable bits, and the gap between the last
+//! enable bits and the context pages.
+
+use core::fmt;
+use core::num::NonZeroU32;
+use core::ptr::NonNull;
+
+use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
+use tock_registers::registers::ReadWrite;
+
+use crate::{
+    CONTEXT_BASE, CONTEXT_NUM, ENABLE_BASE, ENABLE_STRIDE, PENDING_BASE, PLICRegs, Plic,
+    SOURCE_NUM, parse_group_and_field,
+};
+
+/// Set-up steps of a vendor PLIC.
+pub trait PlicVariant: Sync + fmt::Debug {
+    /// Called once by [`Plic::set_variant`], before any context is initialized.
+    fn init(&self, plic: &mut Plic) {
+        let _ = plic;
+    }
+
+    /// Called by [`Plic::init_by_context`] after the baseline initialization of `context`.
+    fn init_context(&self, plic: &mut Plic, ctx: usize) {
+        let _ = (plic, ctx);
+    }
+}
+
+impl Plic {
+    /// Create a new instance of the PLIC from the base address, installing `variant`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be a unique valid pointer to PLIC memory-mapped registers.
+    #[cold]
+    pub unsafe fn new_variant(base: NonNull<PLICRegs>, variant: &'static dyn PlicVariant) -> Self {
+        let mut plic = unsafe { Self::new(base) };
+        plic.set_variant(variant);
+        plic
+    }
+
+    /// Installs `variant` and runs its [`PlicVariant::init`]. From then on
+    /// [`Plic::init_by_context`] runs its [`PlicVariant::init_context`].
+    ///
+    /// [`Plic::new`] installs [`Generic`].
+    #[cold]
+    pub fn set_variant(&mut self, variant: &'static dyn PlicVariant) {
+        self.variant = variant;
+        variant.init(self);
+    }
+
+    /// Returns the 32-bit register at `offset`, which must lie in a reserved window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `offset` is misaligned or overlaps a standard register.
+    fn vendor_reg(&self, offset: usize) -> &ReadWrite<u32> {
+        assert!(
+            offset.is_multiple_of(size_of::<u32>())
+                && (offset == 0
+                    || (PENDING_BASE + SOURCE_NUM / 8..ENABLE_BASE).contains(&offset)
+                    || (ENABLE_BASE + CONTEXT_NUM * ENABLE_STRIDE..CONTEXT_BASE).contains(&offset)),
+            "PLIC: vendor register {offset:#x} overlaps a standard register"
+        );
+        let base = core::ptr::from_ref(self.regs()).cast::<u8>();
+        // SAFETY: the reserved windows lie within the 64 MiB PLIC region, and `offset` is
+        // 4-byte aligned.
+        unsafe { &*base.add(offset).cast::<ReadWrite<u32>>() }
+    }
+}
+
+/// A PLIC following the specification without extensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Generic;
+
+impl PlicVariant for Generic {}
+
+/// The T-HEAD C906/C910 PLIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct THead {
+    /// Value [`PlicVariant::init`] writes to the supervisor access bit, or `None` to leave it
+    /// alone. Only machine mode can write `PLIC_CTRL`, so a supervisor kernel passes `None`
+    /// and relies on its firmware.
+    pub supervisor_access: Option<bool>,
+}
+
+impl THead {
+    /// Offset of `PLIC_CTRL`.
+    pub const CTRL_OFFSET: usize = 0x1F_FFFC;
+    /// `PLIC_CTRL.S_PER`: supervisor mode may access the PLIC.
+    const S_PER: u32 = 1 << 0;
+
+    /// Returns whether supervisor mode may access the PLIC.
+    pub fn supervisor_access(plic: &Plic) -> bool {
+        plic.vendor_reg(Self::CTRL_OFFSET).get() & Self::S_PER != 0
+    }
+
+    /// Allows or denies supervisor mode access to the PLIC, leaving the other bits of
+    /// `PLIC_CTRL` alone. Machine mode only.
+    pub fn set_supervisor_access(plic: &mut Plic, allow: bool) {
+        let reg = plic.vendor_reg(Self::CTRL_OFFSET);
+        let value = reg.get() & !Self::S_PER;
+        reg.set(if allow { value | Self::S_PER } else { value });
+    }
+}
+
+impl PlicVariant for THead {
+    fn init(&self, plic: &mut Plic) {
+        if let Some(allow) = self.supervisor_access {
+            Self::set_supervisor_access(plic, allow);
+        }
+    }
+}
+
+/// Optional behaviour of the Andes NCEPLIC100, see [`Andes::set_features`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AndesFeatures {
+    /// Higher-priority interrupts preempt a hart handling a lower-priority one.
+    pub preemptive: bool,
+    /// The PLIC claims on behalf of the hart and passes the source to its vectored
+    /// interrupt entry; completion is still done by software.
+    pub vectored: bool,
+}
+
+/// How the gateway of an Andes source latches requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Trigger {
+    /// Level-triggered.
+    Level,
+    /// Edge-triggered.
+    Edge,
+}
+
+/// The Andes NCEPLIC100.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Andes {
+    /// Features [`PlicVariant::init`] enables, or `None` to leave them alone.
+    pub features: Option<AndesFeatures>,
+}
+
+impl Andes {
+    /// Offset of the feature enable register.
+    ///
+    /// It aliases the priority register of source 0, which the specification reserves.
+    /// [`Plic`] only takes non-zero sources and never writes it, so [`Andes::set_features`]
+    /// is its only writer.
+    pub const FEATURE_OFFSET: usize = 0x0000;
+    /// Offset of the trigger type registers, one bit per source.
+    pub const TRIGGER_OFFSET: usize = 0x1080;
+    /// Offset of the register giving the number of sources and targets.
+    pub const CONFIG_OFFSET: usize = 0x1100;
+    /// Offset of the register giving the version and maximum priority.
+    pub const VERSION_OFFSET: usize = 0x1104;
+
+    const PREEMPT: u32 = 1 << 0;
+    const VECTORED: u32 = 1 << 1;
+
+    /// Returns the enabled features.
+    pub fn features(plic: &Plic) -> AndesFeatures {
+        let value = plic.vendor_reg(Self::FEATURE_OFFSET).get();
+        AndesFeatures {
+            preemptive: value & Self::PREEMPT != 0,
+            vectored: value & Self::VECTORED != 0,
+        }
+    }
+
+    /// Enables exactly `features`. Bits the implementation lacks read back as 0.
+    pub fn set_features(plic: &mut Plic, features: AndesFeatures) {
+        let mut value = 0;
+        if features.preemptive {
+            value |= Self::PREEMPT;
+        }
+        if features.vectored {
+            value |= Self::VECTORED;
+        }
+        plic.vendor_reg(Self::FEATURE_OFFSET).set(value);
+    }
+
+    /// Returns the trigger type of interrupt `source`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `source` is not below 1024.
+    pub fn trigger(plic: &Plic, source: NonZeroU32) -> Trigger {
+        let word = Self::trigger_reg(plic, source).get();
+        let (_, field) = parse_group_and_field(source.get() as usize);
+        if field.read(word) != 0 {
+            Trigger::Edge
+        } else {
+            Trigger::Level
+        }
+    }
+
+    /// Sets the trigger type of interrupt `source`.
+    ///
+    /// Trigger types are hardwired in some configurations, where this has no effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `source` is not below 1024.
+    pub fn set_trigger(plic: &mut Plic, source: NonZeroU32, trigger: Trigger) {
+        let (_, field) = parse_group_and_field(source.get() as usize);
+        Self::trigger_reg(plic, source).modify(field.val((trigger == Trigger::Edge) as u32));
+    }
+
+    /// Returns the trigger type register holding the bit of `source`.
+    fn trigger_reg(plic: &Plic, source: NonZeroU32) -> &ReadWrite<u32> {
+        assert!(
+            (source.get() as usize) < SOURCE_NUM,
+            "PLIC: interrupt source {source} out of range"
+        );
+        let (group, _) = parse_group_and_field(source.get() as usize);
+        plic.vendor_reg(Self::TRIGGER_OFFSET + group * size_of::<u32>())
+    }
+
+    /// Returns the number of implemented sources and targets (contexts).
+    pub fn config(plic: &Plic) -> (u32, u32) {
+        let value = plic.vendor_reg(Self::CONFIG_OFFSET).get();
+        (value & 0xffff, value >> 16)
+    }
+
+    /// Returns the version and the maximum priority.
+    pub fn version(plic: &Plic) -> (u32, u32) {
+        let value = plic.vendor_reg(Self::VERSION_OFFSET).get();
+        (value & 0xffff, value >> 16)
+    }
+}
+
+impl PlicVariant for Andes {
+    fn init(&self, plic: &mut Plic) {
+        if let Some(features) = self.features {
+            Self::set_features(plic, features);
+        }
+    }
+}